@@ -1,12 +1,25 @@
+use crate::error::Error;
+use crate::path::{PathPattern, Pointer};
+use crate::JsonExpander;
+#[cfg(feature = "cli")]
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Annotation {
     annotation: String,
+    #[serde(default = "enabled_by_default")]
     enabled: bool,
 }
 
+fn enabled_by_default() -> bool {
+    true
+}
+
 impl Annotation {
     /// Creates a new annotation that is enabled by default.
     ///
@@ -118,12 +131,140 @@ impl Display for Annotation {
     }
 }
 
-#[derive(Debug)]
+/// An annotation that cycles through a list of values, one per array item.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "CycleDef")]
+pub struct Cycle {
+    #[serde(flatten)]
+    annotation: Annotation,
+    values: Vec<Value>,
+}
+
+/// A cycle as written in a configuration file. Flattening the annotation
+/// would stop unknown fields from being rejected, so its fields are listed.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CycleDef {
+    annotation: String,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+    values: Vec<Value>,
+}
+
+impl From<CycleDef> for Cycle {
+    fn from(def: CycleDef) -> Self {
+        Cycle {
+            annotation: Annotation::new(def.annotation, def.enabled),
+            values: def.values,
+        }
+    }
+}
+
+impl Cycle {
+    /// Creates a new enabled cycle annotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotation`: The name of the annotation.
+    /// * `values`: The values to cycle through.
+    ///
+    /// returns: Cycle
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// let cycle = jxpand::cfg::Cycle::new("row".to_string(), vec![json!("odd"), json!("even")]);
+    /// assert_eq!(cycle.annotation().annotation(), "row");
+    /// assert_eq!(cycle.values(), &[json!("odd"), json!("even")]);
+    /// ```
+    pub fn new(annotation: String, values: Vec<Value>) -> Self {
+        Cycle {
+            annotation: Annotation::enabled(annotation),
+            values,
+        }
+    }
+
+    /// Gets the annotation for the cycle.
+    pub fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+
+    /// Gets the values the cycle rotates through.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns whether the cycle is enabled and has values to cycle through.
+    pub fn is_enabled(&self) -> bool {
+        self.annotation.is_enabled() && !self.values.is_empty()
+    }
+
+    /// Gets the value of the cycle for the item at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the item in the array.
+    ///
+    /// returns: Option<&Value>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// let cycle = jxpand::cfg::Cycle::new("row".to_string(), vec![json!("odd"), json!("even")]);
+    /// assert_eq!(cycle.value(0), Some(&json!("odd")));
+    /// assert_eq!(cycle.value(1), Some(&json!("even")));
+    /// assert_eq!(cycle.value(2), Some(&json!("odd")));
+    /// ```
+    pub fn value(&self, index: usize) -> Option<&Value> {
+        if self.values.is_empty() {
+            None
+        } else {
+            self.values.get(index % self.values.len())
+        }
+    }
+
+    /// Returns a new cycle with the given prefix applied to the annotation.
+    pub fn prefix(&self, prefix: &str) -> Cycle {
+        Cycle {
+            annotation: self.annotation.prefix(prefix),
+            values: self.values.clone(),
+        }
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.annotation, self.values.len())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "AnnotationsDef")]
 pub struct Annotations {
     count: Annotation,
     first: Annotation,
     last: Annotation,
     index: Annotation,
+    odd: Annotation,
+    even: Annotation,
+    index1: Annotation,
+    rindex: Annotation,
+    only: Annotation,
+    middle: Annotation,
+    empty: Annotation,
+    has_values: Annotation,
+    prev: Annotation,
+    next: Annotation,
+    joiner: Annotation,
+    joiner_value: Value,
+    neighbour_fields: Vec<String>,
+    neighbour_depth: Option<usize>,
+    cycles: Vec<Cycle>,
+    value_key: String,
+    values_key: String,
+    entry_key: String,
 }
 
 impl Annotations {
@@ -143,16 +284,177 @@ impl Annotations {
     pub fn index(&self) -> &Annotation {
         &self.index
     }
+    /// Gets the configuration for the odd annotation.
+    pub fn odd(&self) -> &Annotation {
+        &self.odd
+    }
+    /// Gets the configuration for the even annotation.
+    pub fn even(&self) -> &Annotation {
+        &self.even
+    }
+    /// Gets the configuration for the index1 annotation.
+    pub fn index1(&self) -> &Annotation {
+        &self.index1
+    }
+    /// Gets the configuration for the rindex annotation.
+    pub fn rindex(&self) -> &Annotation {
+        &self.rindex
+    }
+    /// Gets the configuration for the only annotation.
+    pub fn only(&self) -> &Annotation {
+        &self.only
+    }
+    /// Gets the configuration for the middle annotation.
+    pub fn middle(&self) -> &Annotation {
+        &self.middle
+    }
+    /// Gets the configuration for the empty annotation.
+    pub fn empty(&self) -> &Annotation {
+        &self.empty
+    }
+    /// Gets the configuration for the has_values annotation.
+    pub fn has_values(&self) -> &Annotation {
+        &self.has_values
+    }
+    /// Gets the configuration for the prev annotation.
+    pub fn prev(&self) -> &Annotation {
+        &self.prev
+    }
+    /// Gets the configuration for the next annotation.
+    pub fn next(&self) -> &Annotation {
+        &self.next
+    }
+    /// Gets the configuration for the joiner annotation.
+    pub fn joiner(&self) -> &Annotation {
+        &self.joiner
+    }
+    /// Gets the value used for the joiner annotation on all but the last item.
+    pub fn joiner_value(&self) -> &Value {
+        &self.joiner_value
+    }
+    /// Gets the fields of object neighbours kept in the prev and next
+    /// annotations. An empty list keeps every field.
+    pub fn neighbour_fields(&self) -> &[String] {
+        &self.neighbour_fields
+    }
+    /// Gets the number of levels of nested arrays and objects kept in the
+    /// prev and next annotations, if limited.
+    pub fn neighbour_depth(&self) -> Option<usize> {
+        self.neighbour_depth
+    }
+    /// Gets the configured cycle annotations.
+    pub fn cycles(&self) -> &[Cycle] {
+        &self.cycles
+    }
+    /// Gets the key used to hold the original value of a wrapped item.
+    pub fn value_key(&self) -> &str {
+        &self.value_key
+    }
+    /// Gets the key used to hold the items of an array wrapped with a count.
+    pub fn values_key(&self) -> &str {
+        &self.values_key
+    }
+    /// Gets the key used to hold the key of an object entry.
+    pub fn entry_key(&self) -> &str {
+        &self.entry_key
+    }
 
-    /// Returns whether all annotations are disabled.
+    /// Returns whether all built-in annotations are disabled.
     pub fn none(&self) -> bool {
-        !self.count.is_enabled()
-            && !self.first.is_enabled()
-            && !self.last.is_enabled()
-            && !self.index.is_enabled()
+        !self.wraps_arrays() && self.items_none()
+    }
+
+    /// Returns whether arrays are wrapped in an object holding their items
+    /// and array-level annotations such as count and empty.
+    pub fn wraps_arrays(&self) -> bool {
+        self.count.is_enabled() || self.empty.is_enabled() || self.has_values.is_enabled()
+    }
+
+    /// Returns whether all built-in item-level annotations are disabled, as
+    /// reported by the [built-in providers](crate::provider::builtin).
+    ///
+    /// When this is true array items are not wrapped, even if the count
+    /// annotation is enabled, unless a custom provider is registered with
+    /// [`JsonExpander::with_provider`](crate::JsonExpander::with_provider).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut annotations = jxpand::cfg::Annotations::default();
+    /// annotations.disable_first();
+    /// annotations.disable_last();
+    /// annotations.disable_index();
+    /// assert!(annotations.items_none());
+    /// assert!(!annotations.none());
+    /// annotations.enable_odd();
+    /// assert!(!annotations.items_none());
+    /// ```
+    pub fn items_none(&self) -> bool {
+        !crate::provider::builtin()
+            .iter()
+            .any(|provider| provider.is_enabled(self))
+    }
+
+    /// Checks that no annotation or wrapper key is empty and that no two of
+    /// them share a key, which would silently overwrite one another.
+    ///
+    /// Disabled annotations are not checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`: The prefix that will be applied to the keys, if any.
+    ///
+    /// returns: Result<(), String>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut annotations = jxpand::cfg::Annotations::default();
+    /// assert!(annotations.validate("").is_ok());
+    /// annotations.set_first_annotation("index");
+    /// assert!(annotations.validate("").is_err());
+    /// ```
+    pub fn validate(&self, prefix: &str) -> Result<(), String> {
+        let enabled = |annotation: &Annotation| -> Option<String> {
+            annotation.is_enabled().then(|| annotation.annotation())
+        };
+        let item_keys = [
+            Some(self.value_key.clone()),
+            Some(self.entry_key.clone()),
+            enabled(&self.first),
+            enabled(&self.last),
+            enabled(&self.index),
+            enabled(&self.odd),
+            enabled(&self.even),
+            enabled(&self.index1),
+            enabled(&self.rindex),
+            enabled(&self.only),
+            enabled(&self.middle),
+            enabled(&self.prev),
+            enabled(&self.next),
+            enabled(&self.joiner),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(
+            self.cycles
+                .iter()
+                .filter(|c| c.is_enabled())
+                .map(|c| c.annotation.annotation()),
+        );
+        let count_keys = [
+            Some(self.values_key.clone()),
+            enabled(&self.count),
+            enabled(&self.empty),
+            enabled(&self.has_values),
+        ]
+        .into_iter()
+        .flatten();
+        check_keys(item_keys, prefix)?;
+        check_keys(count_keys, prefix)
     }
 
-    /// Applies a prefix to all annotations.
+    /// Applies a prefix to all annotations and wrapper keys.
     ///
     /// # Arguments
     ///
@@ -169,6 +471,10 @@ impl Annotations {
     /// assert_eq!(prefixed.first().to_string(), "foo_first(enabled)");
     /// assert_eq!(prefixed.last().to_string(), "foo_last(enabled)");
     /// assert_eq!(prefixed.index().to_string(), "foo_index(enabled)");
+    /// assert_eq!(prefixed.joiner().to_string(), "foo_joiner(disabled)");
+    /// assert_eq!(prefixed.value_key(), "foo_value");
+    /// assert_eq!(prefixed.values_key(), "foo_values");
+    /// assert_eq!(prefixed.entry_key(), "foo_key");
     /// ```
     pub fn prefix(&self, prefix: &str) -> Annotations {
         Annotations {
@@ -176,6 +482,24 @@ impl Annotations {
             first: self.first.prefix(prefix),
             last: self.last.prefix(prefix),
             index: self.index.prefix(prefix),
+            odd: self.odd.prefix(prefix),
+            even: self.even.prefix(prefix),
+            index1: self.index1.prefix(prefix),
+            rindex: self.rindex.prefix(prefix),
+            only: self.only.prefix(prefix),
+            middle: self.middle.prefix(prefix),
+            empty: self.empty.prefix(prefix),
+            has_values: self.has_values.prefix(prefix),
+            prev: self.prev.prefix(prefix),
+            next: self.next.prefix(prefix),
+            joiner: self.joiner.prefix(prefix),
+            joiner_value: self.joiner_value.clone(),
+            neighbour_fields: self.neighbour_fields.clone(),
+            neighbour_depth: self.neighbour_depth,
+            cycles: self.cycles.iter().map(|c| c.prefix(prefix)).collect(),
+            value_key: format!("{}{}", prefix, self.value_key),
+            values_key: format!("{}{}", prefix, self.values_key),
+            entry_key: format!("{}{}", prefix, self.entry_key),
         }
     }
 
@@ -185,6 +509,20 @@ impl Annotations {
         self.first.enabled = false;
         self.last.enabled = false;
         self.index.enabled = false;
+        self.odd.enabled = false;
+        self.even.enabled = false;
+        self.index1.enabled = false;
+        self.rindex.enabled = false;
+        self.only.enabled = false;
+        self.middle.enabled = false;
+        self.empty.enabled = false;
+        self.has_values.enabled = false;
+        self.prev.enabled = false;
+        self.next.enabled = false;
+        self.joiner.enabled = false;
+        for cycle in &mut self.cycles {
+            cycle.annotation.enabled = false;
+        }
     }
 
     /// Enables all annotations.
@@ -193,6 +531,20 @@ impl Annotations {
         self.first.enabled = true;
         self.last.enabled = true;
         self.index.enabled = true;
+        self.odd.enabled = true;
+        self.even.enabled = true;
+        self.index1.enabled = true;
+        self.rindex.enabled = true;
+        self.only.enabled = true;
+        self.middle.enabled = true;
+        self.empty.enabled = true;
+        self.has_values.enabled = true;
+        self.prev.enabled = true;
+        self.next.enabled = true;
+        self.joiner.enabled = true;
+        for cycle in &mut self.cycles {
+            cycle.annotation.enabled = true;
+        }
     }
 
     /// Disables the count annotation.
@@ -235,6 +587,184 @@ impl Annotations {
         self.index.enabled = true;
     }
 
+    /// Disables the odd annotation.
+    pub fn disable_odd(&mut self) {
+        self.odd.enabled = false;
+    }
+
+    /// Enables the odd annotation, which is true for items at an odd position, starting with the first.
+    pub fn enable_odd(&mut self) {
+        self.odd.enabled = true;
+    }
+
+    /// Disables the even annotation.
+    pub fn disable_even(&mut self) {
+        self.even.enabled = false;
+    }
+
+    /// Enables the even annotation, which is true for items at an even position, starting with the second.
+    pub fn enable_even(&mut self) {
+        self.even.enabled = true;
+    }
+
+    /// Disables the index1 annotation.
+    pub fn disable_index1(&mut self) {
+        self.index1.enabled = false;
+    }
+
+    /// Enables the index1 annotation, which is the one-based position of the item.
+    pub fn enable_index1(&mut self) {
+        self.index1.enabled = true;
+    }
+
+    /// Disables the rindex annotation.
+    pub fn disable_rindex(&mut self) {
+        self.rindex.enabled = false;
+    }
+
+    /// Enables the rindex annotation, which is the number of items after the item.
+    pub fn enable_rindex(&mut self) {
+        self.rindex.enabled = true;
+    }
+
+    /// Disables the only annotation.
+    pub fn disable_only(&mut self) {
+        self.only.enabled = false;
+    }
+
+    /// Enables the only annotation, which is true if the item is the only item.
+    pub fn enable_only(&mut self) {
+        self.only.enabled = true;
+    }
+
+    /// Disables the middle annotation.
+    pub fn disable_middle(&mut self) {
+        self.middle.enabled = false;
+    }
+
+    /// Enables the middle annotation, which is true for items that are neither first nor last.
+    pub fn enable_middle(&mut self) {
+        self.middle.enabled = true;
+    }
+
+    /// Disables the empty annotation.
+    pub fn disable_empty(&mut self) {
+        self.empty.enabled = false;
+    }
+
+    /// Enables the empty annotation, which is true for arrays without items.
+    pub fn enable_empty(&mut self) {
+        self.empty.enabled = true;
+    }
+
+    /// Disables the has_values annotation.
+    pub fn disable_has_values(&mut self) {
+        self.has_values.enabled = false;
+    }
+
+    /// Enables the has_values annotation, which is true for arrays with at least one item.
+    pub fn enable_has_values(&mut self) {
+        self.has_values.enabled = true;
+    }
+
+    /// Disables the prev annotation.
+    pub fn disable_prev(&mut self) {
+        self.prev.enabled = false;
+    }
+
+    /// Enables the prev annotation, which holds the previous item.
+    pub fn enable_prev(&mut self) {
+        self.prev.enabled = true;
+    }
+
+    /// Disables the next annotation.
+    pub fn disable_next(&mut self) {
+        self.next.enabled = false;
+    }
+
+    /// Enables the next annotation, which holds the next item.
+    pub fn enable_next(&mut self) {
+        self.next.enabled = true;
+    }
+
+    /// Disables the joiner annotation.
+    pub fn disable_joiner(&mut self) {
+        self.joiner.enabled = false;
+    }
+
+    /// Enables the joiner annotation.
+    pub fn enable_joiner(&mut self) {
+        self.joiner.enabled = true;
+    }
+
+    /// Sets the value of the joiner annotation.
+    ///
+    /// The value is added to every item in an array except the last, which
+    /// receives `null` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: The value to add to all but the last item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut annotations = jxpand::cfg::Annotations::default();
+    /// annotations.set_joiner_value(serde_json::json!(", "));
+    /// assert_eq!(annotations.joiner_value(), &serde_json::json!(", "));
+    /// ```
+    pub fn set_joiner_value(&mut self, value: Value) {
+        self.joiner_value = value;
+    }
+
+    /// Sets the fields of object neighbours to keep in the prev and next
+    /// annotations, so large objects are not copied in full. An empty list
+    /// keeps every field.
+    pub fn set_neighbour_fields(&mut self, fields: Vec<String>) {
+        self.neighbour_fields = fields;
+    }
+
+    /// Sets the number of levels of nested arrays and objects to keep in the
+    /// prev and next annotations. Deeper values are left out, and a depth of
+    /// 0 keeps only scalar neighbours.
+    pub fn set_neighbour_depth(&mut self, depth: Option<usize>) {
+        self.neighbour_depth = depth;
+    }
+
+    /// Adds a cycle annotation, replacing any existing cycle with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the cycle annotation.
+    /// * `values`: The values to cycle through.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// let mut annotations = jxpand::cfg::Annotations::default();
+    /// annotations.add_cycle("row", vec![json!("odd"), json!("even")]);
+    /// annotations.add_cycle("row", vec![json!("a"), json!("b"), json!("c")]);
+    /// assert_eq!(annotations.cycles().len(), 1);
+    /// assert_eq!(annotations.cycles()[0].values().len(), 3);
+    /// ```
+    pub fn add_cycle(&mut self, name: &str, values: Vec<Value>) {
+        let cycle = Cycle::new(name.to_string(), values);
+        match self
+            .cycles
+            .iter_mut()
+            .find(|c| c.annotation.annotation == name)
+        {
+            Some(existing) => *existing = cycle,
+            None => self.cycles.push(cycle),
+        }
+    }
+
+    /// Removes the cycle annotation with the given name.
+    pub fn remove_cycle(&mut self, name: &str) {
+        self.cycles.retain(|c| c.annotation.annotation != name);
+    }
+
     /// Sets the name of the count annotation.
     pub fn set_count_annotation(&mut self, name: &str) {
         self.count.annotation = name.to_string();
@@ -254,6 +784,92 @@ impl Annotations {
     pub fn set_index_annotation(&mut self, name: &str) {
         self.index.annotation = name.to_string();
     }
+
+    /// Sets the name of the odd annotation.
+    pub fn set_odd_annotation(&mut self, name: &str) {
+        self.odd.annotation = name.to_string();
+    }
+
+    /// Sets the name of the even annotation.
+    pub fn set_even_annotation(&mut self, name: &str) {
+        self.even.annotation = name.to_string();
+    }
+
+    /// Sets the name of the index1 annotation.
+    pub fn set_index1_annotation(&mut self, name: &str) {
+        self.index1.annotation = name.to_string();
+    }
+
+    /// Sets the name of the rindex annotation.
+    pub fn set_rindex_annotation(&mut self, name: &str) {
+        self.rindex.annotation = name.to_string();
+    }
+
+    /// Sets the name of the only annotation.
+    pub fn set_only_annotation(&mut self, name: &str) {
+        self.only.annotation = name.to_string();
+    }
+
+    /// Sets the name of the middle annotation.
+    pub fn set_middle_annotation(&mut self, name: &str) {
+        self.middle.annotation = name.to_string();
+    }
+
+    /// Sets the name of the empty annotation.
+    pub fn set_empty_annotation(&mut self, name: &str) {
+        self.empty.annotation = name.to_string();
+    }
+
+    /// Sets the name of the has_values annotation.
+    pub fn set_has_values_annotation(&mut self, name: &str) {
+        self.has_values.annotation = name.to_string();
+    }
+
+    /// Sets the name of the prev annotation.
+    pub fn set_prev_annotation(&mut self, name: &str) {
+        self.prev.annotation = name.to_string();
+    }
+
+    /// Sets the name of the next annotation.
+    pub fn set_next_annotation(&mut self, name: &str) {
+        self.next.annotation = name.to_string();
+    }
+
+    /// Sets the name of the joiner annotation.
+    pub fn set_joiner_annotation(&mut self, name: &str) {
+        self.joiner.annotation = name.to_string();
+    }
+
+    /// Sets the key used to hold the original value of a wrapped item.
+    pub fn set_value_key(&mut self, name: &str) {
+        self.value_key = name.to_string();
+    }
+
+    /// Sets the key used to hold the items of an array wrapped with a count.
+    pub fn set_values_key(&mut self, name: &str) {
+        self.values_key = name.to_string();
+    }
+
+    /// Sets the key used to hold the key of an object entry.
+    pub fn set_entry_key(&mut self, name: &str) {
+        self.entry_key = name.to_string();
+    }
+}
+
+/// Fails if any of the keys is empty or is used more than once.
+fn check_keys(keys: impl Iterator<Item = String>, prefix: &str) -> Result<(), String> {
+    let mut seen = Vec::new();
+    for key in keys {
+        if key.is_empty() {
+            return Err("annotation names and wrapper keys must not be empty".to_string());
+        }
+        let key = format!("{}{}", prefix, key);
+        if seen.contains(&key) {
+            return Err(format!("'{}' is used by more than one annotation", key));
+        }
+        seen.push(key);
+    }
+    Ok(())
 }
 
 impl Default for Annotations {
@@ -263,12 +879,141 @@ impl Default for Annotations {
             first: Annotation::enabled("first".to_string()),
             last: Annotation::enabled("last".to_string()),
             index: Annotation::enabled("index".to_string()),
+            odd: Annotation::disabled("odd".to_string()),
+            even: Annotation::disabled("even".to_string()),
+            index1: Annotation::disabled("index1".to_string()),
+            rindex: Annotation::disabled("rindex".to_string()),
+            only: Annotation::disabled("only".to_string()),
+            middle: Annotation::disabled("middle".to_string()),
+            empty: Annotation::disabled("empty".to_string()),
+            has_values: Annotation::disabled("has_values".to_string()),
+            prev: Annotation::disabled("prev".to_string()),
+            next: Annotation::disabled("next".to_string()),
+            joiner: Annotation::disabled("joiner".to_string()),
+            joiner_value: Value::String(",".to_string()),
+            neighbour_fields: Vec::new(),
+            neighbour_depth: None,
+            cycles: Vec::new(),
+            value_key: "value".to_string(),
+            values_key: "values".to_string(),
+            entry_key: "key".to_string(),
+        }
+    }
+}
+
+/// The settings for an annotation as written in a configuration file.
+///
+/// An annotation can be given as a boolean to enable or disable it, or as a
+/// table with an optional `annotation` name and `enabled` flag.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnnotationDef {
+    Enabled(bool),
+    Table(AnnotationTableDef),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationTableDef {
+    annotation: Option<String>,
+    enabled: Option<bool>,
+}
+
+impl AnnotationDef {
+    fn apply(self, annotation: &mut Annotation) {
+        match self {
+            AnnotationDef::Enabled(enabled) => annotation.enabled = enabled,
+            AnnotationDef::Table(table) => {
+                if let Some(name) = table.annotation {
+                    annotation.annotation = name;
+                }
+                if let Some(enabled) = table.enabled {
+                    annotation.enabled = enabled;
+                }
+            }
+        }
+    }
+}
+
+/// The annotation settings as written in a configuration file, where any
+/// setting that is left out keeps its default.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationsDef {
+    count: Option<AnnotationDef>,
+    first: Option<AnnotationDef>,
+    last: Option<AnnotationDef>,
+    index: Option<AnnotationDef>,
+    odd: Option<AnnotationDef>,
+    even: Option<AnnotationDef>,
+    index1: Option<AnnotationDef>,
+    rindex: Option<AnnotationDef>,
+    only: Option<AnnotationDef>,
+    middle: Option<AnnotationDef>,
+    empty: Option<AnnotationDef>,
+    has_values: Option<AnnotationDef>,
+    prev: Option<AnnotationDef>,
+    next: Option<AnnotationDef>,
+    joiner: Option<AnnotationDef>,
+    joiner_value: Option<Value>,
+    #[serde(default)]
+    neighbour_fields: Vec<String>,
+    neighbour_depth: Option<usize>,
+    #[serde(default)]
+    cycles: Vec<Cycle>,
+    value_key: Option<String>,
+    values_key: Option<String>,
+    entry_key: Option<String>,
+}
+
+impl From<AnnotationsDef> for Annotations {
+    fn from(def: AnnotationsDef) -> Self {
+        let mut annotations = Annotations::default();
+        let overrides = [
+            (def.count, &mut annotations.count),
+            (def.first, &mut annotations.first),
+            (def.last, &mut annotations.last),
+            (def.index, &mut annotations.index),
+            (def.odd, &mut annotations.odd),
+            (def.even, &mut annotations.even),
+            (def.index1, &mut annotations.index1),
+            (def.rindex, &mut annotations.rindex),
+            (def.only, &mut annotations.only),
+            (def.middle, &mut annotations.middle),
+            (def.empty, &mut annotations.empty),
+            (def.has_values, &mut annotations.has_values),
+            (def.prev, &mut annotations.prev),
+            (def.next, &mut annotations.next),
+            (def.joiner, &mut annotations.joiner),
+        ];
+        for (def, annotation) in overrides {
+            if let Some(def) = def {
+                def.apply(annotation);
+            }
+        }
+        if let Some(joiner_value) = def.joiner_value {
+            annotations.joiner_value = joiner_value;
+        }
+        annotations.neighbour_fields = def.neighbour_fields;
+        annotations.neighbour_depth = def.neighbour_depth;
+        annotations.cycles = def.cycles;
+        if let Some(value_key) = def.value_key {
+            annotations.value_key = value_key;
+        }
+        if let Some(values_key) = def.values_key {
+            annotations.values_key = values_key;
         }
+        if let Some(entry_key) = def.entry_key {
+            annotations.entry_key = entry_key;
+        }
+        annotations
     }
 }
 
 /// The mode to use when annotating objects.
-#[derive(Clone, Debug, Default, ValueEnum)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
 pub enum AnnotationMode {
     /// Wrap the object in a wrapper object containing the annotations.
     #[default]
@@ -277,11 +1022,297 @@ pub enum AnnotationMode {
     Merge,
 }
 
-#[derive(Debug)]
+impl AnnotationMode {
+    const NAMES: &'static [&'static str] = &["wrap", "merge"];
+}
+
+impl Display for AnnotationMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AnnotationMode::Wrap => "wrap",
+            AnnotationMode::Merge => "merge",
+        })
+    }
+}
+
+impl FromStr for AnnotationMode {
+    type Err = ParseSettingError;
+
+    /// Parses an annotation mode from its name, as used in configuration files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::cfg::AnnotationMode;
+    /// let mode: AnnotationMode = "merge".parse().unwrap();
+    /// assert_eq!(mode.to_string(), "merge");
+    /// assert!("nest".parse::<AnnotationMode>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(AnnotationMode::Wrap),
+            "merge" => Ok(AnnotationMode::Merge),
+            _ => Err(ParseSettingError::new(s, AnnotationMode::NAMES)),
+        }
+    }
+}
+
+/// How to handle an annotation whose key is already present in an object.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Fail with an error naming the location of the conflicting key.
+    Error,
+    /// Keep the existing value and leave out the annotation.
+    Skip,
+    /// Replace the existing value with the annotation.
+    #[default]
+    Overwrite,
+    /// Add the annotation under the first free key with a numeric suffix (e.g. `_index_1`).
+    Suffix,
+}
+
+impl CollisionPolicy {
+    const NAMES: &'static [&'static str] = &["error", "skip", "overwrite", "suffix"];
+}
+
+impl Display for CollisionPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CollisionPolicy::Error => "error",
+            CollisionPolicy::Skip => "skip",
+            CollisionPolicy::Overwrite => "overwrite",
+            CollisionPolicy::Suffix => "suffix",
+        })
+    }
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = ParseSettingError;
+
+    /// Parses a collision policy from its name, as used in configuration files.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(CollisionPolicy::Error),
+            "skip" => Ok(CollisionPolicy::Skip),
+            "overwrite" => Ok(CollisionPolicy::Overwrite),
+            "suffix" => Ok(CollisionPolicy::Suffix),
+            _ => Err(ParseSettingError::new(s, CollisionPolicy::NAMES)),
+        }
+    }
+}
+
+/// An error returned when a string is not the name of an [`AnnotationMode`]
+/// or [`CollisionPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSettingError {
+    value: String,
+    expected: &'static [&'static str],
+}
+
+impl ParseSettingError {
+    fn new(value: &str, expected: &'static [&'static str]) -> Self {
+        ParseSettingError {
+            value: value.to_string(),
+            expected,
+        }
+    }
+}
+
+impl Display for ParseSettingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid value '{}': expected one of '{}'",
+            self.value,
+            self.expected.join("', '")
+        )
+    }
+}
+
+impl std::error::Error for ParseSettingError {}
+
+/// The objects to convert into an annotated array of key/value entries.
+///
+/// In a configuration file this is either `"none"`, `"all"` or a list of path patterns.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "EntriesDef", into = "EntriesDef")]
+pub enum Entries {
+    /// Leave objects as objects.
+    #[default]
+    None,
+    /// Convert every object into entries.
+    All,
+    /// Convert only the objects at locations matching the given patterns into entries.
+    At(Vec<PathPattern>),
+}
+
+impl Entries {
+    /// Returns whether the object at the given location should be converted into entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The location of the object within the document.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::cfg::Entries;
+    /// use jxpand::path::Pointer;
+    /// let entries = Entries::At(vec!["/enums".parse().unwrap()]);
+    /// assert!(entries.matches(&"/enums".parse().unwrap()));
+    /// assert!(!entries.matches(&Pointer::root()));
+    /// assert!(Entries::All.matches(&Pointer::root()));
+    /// ```
+    pub fn matches(&self, path: &Pointer) -> bool {
+        match self {
+            Entries::None => false,
+            Entries::All => true,
+            Entries::At(patterns) => patterns.iter().any(|p| p.matches(path)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum EntriesDef {
+    Keyword(String),
+    At(Vec<PathPattern>),
+}
+
+impl TryFrom<EntriesDef> for Entries {
+    type Error = String;
+
+    fn try_from(def: EntriesDef) -> Result<Self, Self::Error> {
+        match def {
+            EntriesDef::Keyword(keyword) => match keyword.as_str() {
+                "none" => Ok(Entries::None),
+                "all" => Ok(Entries::All),
+                _ => Err(format!(
+                    "expected \"none\", \"all\" or a list of path patterns, found \"{}\"",
+                    keyword
+                )),
+            },
+            EntriesDef::At(paths) => Ok(Entries::At(paths)),
+        }
+    }
+}
+
+impl From<Entries> for EntriesDef {
+    fn from(entries: Entries) -> Self {
+        match entries {
+            Entries::None => EntriesDef::Keyword("none".to_string()),
+            Entries::All => EntriesDef::Keyword("all".to_string()),
+            Entries::At(paths) => EntriesDef::At(paths),
+        }
+    }
+}
+
+fn default_prefix() -> String {
+    "_".to_string()
+}
+
+/// Annotation settings that apply to arrays and entries at locations matching a pattern.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    path: PathPattern,
+    #[serde(default)]
+    annotations: Annotations,
+    #[serde(default = "default_prefix")]
+    annotation_prefix: String,
+    #[serde(default)]
+    object_mode: AnnotationMode,
+}
+
+impl Rule {
+    /// Creates a new rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The pattern matching the locations the rule applies to.
+    /// * `annotations`: The annotation configuration to use.
+    /// * `annotation_prefix`: The prefix to apply to annotations.
+    /// * `object_mode`: The mode to use when annotating objects.
+    ///
+    /// returns: Rule
+    pub fn new(
+        path: PathPattern,
+        annotations: Annotations,
+        annotation_prefix: String,
+        object_mode: AnnotationMode,
+    ) -> Self {
+        Rule {
+            path,
+            annotations,
+            annotation_prefix,
+            object_mode,
+        }
+    }
+    /// Gets the pattern matching the locations the rule applies to.
+    pub fn path(&self) -> &PathPattern {
+        &self.path
+    }
+    /// Gets the configuration for the annotations.
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+    /// Gets the prefix to use for annotations.
+    pub fn annotation_prefix(&self) -> &str {
+        &self.annotation_prefix
+    }
+    /// Gets the mode to use when annotating objects.
+    pub fn object_mode(&self) -> &AnnotationMode {
+        &self.object_mode
+    }
+
+    fn resolve(self) -> Rule {
+        Rule {
+            annotations: match self.object_mode {
+                AnnotationMode::Wrap => self.annotations,
+                AnnotationMode::Merge => self.annotations.prefix(&self.annotation_prefix),
+            },
+            ..self
+        }
+    }
+}
+
+/// The configuration for an expander.
+///
+/// Configurations can be loaded from a file using serde. Any setting that is
+/// left out keeps its default.
+///
+/// # Examples
+///
+/// ```
+/// let config: jxpand::cfg::Config = toml::from_str(r#"
+///     object_mode = "merge"
+///
+///     [annotations]
+///     count = false
+///     index = { annotation = "position" }
+/// "#).unwrap();
+/// assert!(!config.annotations().count().is_enabled());
+/// assert_eq!(config.annotations().index().annotation(), "position");
+/// assert_eq!(config.annotation_prefix(), "_");
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     annotations: Annotations,
     annotation_prefix: String,
     object_mode: AnnotationMode,
+    entries: Entries,
+    sort_entries: bool,
+    collision_policy: CollisionPolicy,
+    rules: Vec<Rule>,
+    include: Vec<PathPattern>,
+    exclude: Vec<PathPattern>,
+    max_depth: Option<usize>,
+    #[serde(skip)]
     resolved: bool,
 }
 
@@ -304,6 +1335,13 @@ impl Config {
             annotations,
             annotation_prefix,
             object_mode,
+            entries: Entries::default(),
+            sort_entries: false,
+            collision_policy: CollisionPolicy::default(),
+            rules: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
             resolved: false,
         }
     }
@@ -311,6 +1349,10 @@ impl Config {
     pub fn annotations(&self) -> &Annotations {
         &self.annotations
     }
+    /// Gets a mutable reference to the configuration for the annotations.
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
+    }
     /// Gets the prefix to use for annotations.
     pub fn annotation_prefix(&self) -> &str {
         &self.annotation_prefix
@@ -319,6 +1361,168 @@ impl Config {
     pub fn object_mode(&self) -> &AnnotationMode {
         &self.object_mode
     }
+    /// Gets the objects to convert into entries.
+    pub fn entries(&self) -> &Entries {
+        &self.entries
+    }
+    /// Gets whether entries are sorted by key.
+    pub fn sort_entries(&self) -> bool {
+        self.sort_entries
+    }
+    /// Gets the policy for annotations that collide with existing keys.
+    pub fn collision_policy(&self) -> &CollisionPolicy {
+        &self.collision_policy
+    }
+    /// Gets the rules that apply to specific locations.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+    /// Gets the patterns for the locations to annotate. An empty list annotates everything.
+    pub fn include(&self) -> &[PathPattern] {
+        &self.include
+    }
+    /// Gets the patterns for the locations to leave untouched.
+    pub fn exclude(&self) -> &[PathPattern] {
+        &self.exclude
+    }
+    /// Gets the maximum nesting depth of arrays and objects, if limited.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Gets the most specific rule matching the given location, if any.
+    ///
+    /// When several rules are equally specific the last one wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::cfg::{AnnotationMode, Annotations, Config, Rule};
+    /// let mut config = Config::default();
+    /// for path in ["/services/*/ports", "/services/web/ports"] {
+    ///     let rule = Rule::new(path.parse().unwrap(), Annotations::default(), "_".to_string(), AnnotationMode::Wrap);
+    ///     config.add_rule(rule);
+    /// }
+    /// let rule = config.rule_for(&"/services/web/ports".parse().unwrap()).unwrap();
+    /// assert_eq!(rule.path().to_string(), "/services/web/ports");
+    /// assert!(config.rule_for(&"/services".parse().unwrap()).is_none());
+    /// ```
+    pub fn rule_for(&self, path: &Pointer) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.path.matches(path))
+            .max_by_key(|rule| rule.path.specificity())
+    }
+
+    /// Returns whether the array or object at the given location should be annotated.
+    pub fn is_included(&self, path: &Pointer) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| p.matches(path))
+    }
+
+    /// Returns whether the value at the given location, and everything within
+    /// it, should be left untouched.
+    pub fn is_excluded(&self, path: &Pointer) -> bool {
+        self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    /// Sets the prefix to use for annotations in merge mode.
+    pub fn set_annotation_prefix(&mut self, annotation_prefix: &str) {
+        self.annotation_prefix = annotation_prefix.to_string();
+    }
+
+    /// Sets the mode to use when annotating objects.
+    pub fn set_object_mode(&mut self, object_mode: AnnotationMode) {
+        self.object_mode = object_mode;
+    }
+
+    /// Sets the objects to convert into an annotated array of entries.
+    ///
+    /// Each entry is an object containing the `key` and `value` of a member
+    /// of the original object, along with the item annotations.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries`: The objects to convert.
+    pub fn set_entries(&mut self, entries: Entries) {
+        self.entries = entries;
+    }
+
+    /// Sets whether entries are sorted by key rather than kept in document order.
+    ///
+    /// Document order is only known when the `preserve_order` feature is
+    /// enabled. Without it, objects keep their keys sorted, so entries are
+    /// always in key order.
+    pub fn set_sort_entries(&mut self, sort_entries: bool) {
+        self.sort_entries = sort_entries;
+    }
+
+    /// Sets the policy for annotations that collide with existing keys.
+    ///
+    /// Collisions typically occur in merge mode, when an object already contains
+    /// a key with the same name as an annotation.
+    pub fn set_collision_policy(&mut self, collision_policy: CollisionPolicy) {
+        self.collision_policy = collision_policy;
+    }
+
+    /// Adds a rule that applies its own annotation settings to matching locations.
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// Sets the patterns for the locations to annotate.
+    ///
+    /// If any patterns are given, only arrays and entries at matching locations
+    /// are annotated. Values elsewhere are still searched for matches.
+    pub fn set_include(&mut self, include: Vec<PathPattern>) {
+        self.include = include;
+    }
+
+    /// Sets the patterns for the locations to leave untouched, including
+    /// everything within them.
+    pub fn set_exclude(&mut self, exclude: Vec<PathPattern>) {
+        self.exclude = exclude;
+    }
+
+    /// Sets the maximum nesting depth of arrays and objects.
+    ///
+    /// The root array or object has a depth of 1. Expanding a document with
+    /// deeper nesting fails with a [`DepthError`](crate::error::DepthError).
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    /// Checks that the annotations at the top level and in every rule have
+    /// distinct, non-empty keys once the prefix for their mode is applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut config = jxpand::cfg::Config::default();
+    /// config.annotations_mut().set_count_annotation("values");
+    /// let error = config.validate().unwrap_err();
+    /// assert_eq!(error.to_string(), "'values' is used by more than one annotation");
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        // Once resolved, the prefix has already been applied to the annotations.
+        let prefix = |object_mode, prefix| match self.resolved {
+            true => "",
+            false => effective_prefix(object_mode, prefix),
+        };
+        self.annotations
+            .validate(prefix(&self.object_mode, &self.annotation_prefix))
+            .map_err(Error::Config)?;
+        for rule in &self.rules {
+            rule.annotations
+                .validate(prefix(&rule.object_mode, &rule.annotation_prefix))
+                .map_err(|e| Error::config(format!("rule for '{}': {}", rule.path, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Creates a builder for a configuration, starting from the defaults.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
 
     /// Returns a new configuration with the prefix applied to all annotations
     /// depending on the mode.
@@ -334,18 +1538,323 @@ impl Config {
             },
             annotation_prefix: self.annotation_prefix,
             object_mode: self.object_mode,
+            entries: self.entries,
+            sort_entries: self.sort_entries,
+            collision_policy: self.collision_policy,
+            rules: self.rules.into_iter().map(Rule::resolve).collect(),
+            include: self.include,
+            exclude: self.exclude,
+            max_depth: self.max_depth,
             resolved: true,
         }
     }
 }
 
+/// Gets the prefix that is applied to annotations in the given mode.
+fn effective_prefix<'a>(object_mode: &AnnotationMode, prefix: &'a str) -> &'a str {
+    match object_mode {
+        AnnotationMode::Wrap => "",
+        AnnotationMode::Merge => prefix,
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             annotations: Annotations::default(),
             annotation_prefix: "_".to_string(),
             object_mode: AnnotationMode::default(),
+            entries: Entries::default(),
+            sort_entries: false,
+            collision_policy: CollisionPolicy::default(),
+            rules: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
             resolved: false,
         }
     }
 }
+
+/// A fluent builder for a [`Config`].
+///
+/// Unlike mutating a [`Config`] directly, [`ConfigBuilder::build`] validates
+/// the result and fails if annotations would overwrite one another.
+///
+/// # Examples
+///
+/// ```
+/// use jxpand::cfg::AnnotationMode;
+/// use jxpand::Expander;
+/// let expander = jxpand::JsonExpander::builder()
+///     .object_mode(AnnotationMode::Merge)
+///     .annotation_prefix("$")
+///     .count(false)
+///     .index_annotation("position")
+///     .build_expander()
+///     .unwrap();
+/// let expanded = expander.expand(serde_json::json!([{"a": 1}]));
+/// assert_eq!(expanded, serde_json::json!([{"a": 1, "$first": true, "$last": true, "$position": 0}]));
+///
+/// let error = jxpand::cfg::Config::builder().first_annotation("last").build();
+/// assert!(error.is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Replaces all annotation settings.
+    pub fn annotations(mut self, annotations: Annotations) -> Self {
+        self.config.annotations = annotations;
+        self
+    }
+    /// Sets the prefix to use for annotations in merge mode.
+    pub fn annotation_prefix(mut self, annotation_prefix: &str) -> Self {
+        self.config.annotation_prefix = annotation_prefix.to_string();
+        self
+    }
+    /// Sets the mode to use when annotating objects.
+    pub fn object_mode(mut self, object_mode: AnnotationMode) -> Self {
+        self.config.object_mode = object_mode;
+        self
+    }
+    /// Enables or disables the count annotation.
+    pub fn count(mut self, enabled: bool) -> Self {
+        self.config.annotations.count.enabled = enabled;
+        self
+    }
+    /// Enables or disables the first annotation.
+    pub fn first(mut self, enabled: bool) -> Self {
+        self.config.annotations.first.enabled = enabled;
+        self
+    }
+    /// Enables or disables the last annotation.
+    pub fn last(mut self, enabled: bool) -> Self {
+        self.config.annotations.last.enabled = enabled;
+        self
+    }
+    /// Enables or disables the index annotation.
+    pub fn index(mut self, enabled: bool) -> Self {
+        self.config.annotations.index.enabled = enabled;
+        self
+    }
+    /// Enables or disables the odd annotation.
+    pub fn odd(mut self, enabled: bool) -> Self {
+        self.config.annotations.odd.enabled = enabled;
+        self
+    }
+    /// Enables or disables the even annotation.
+    pub fn even(mut self, enabled: bool) -> Self {
+        self.config.annotations.even.enabled = enabled;
+        self
+    }
+    /// Enables or disables the index1 annotation.
+    pub fn index1(mut self, enabled: bool) -> Self {
+        self.config.annotations.index1.enabled = enabled;
+        self
+    }
+    /// Enables or disables the rindex annotation.
+    pub fn rindex(mut self, enabled: bool) -> Self {
+        self.config.annotations.rindex.enabled = enabled;
+        self
+    }
+    /// Enables or disables the only annotation.
+    pub fn only(mut self, enabled: bool) -> Self {
+        self.config.annotations.only.enabled = enabled;
+        self
+    }
+    /// Enables or disables the middle annotation.
+    pub fn middle(mut self, enabled: bool) -> Self {
+        self.config.annotations.middle.enabled = enabled;
+        self
+    }
+    /// Enables or disables the empty annotation.
+    pub fn empty(mut self, enabled: bool) -> Self {
+        self.config.annotations.empty.enabled = enabled;
+        self
+    }
+    /// Enables or disables the has_values annotation.
+    pub fn has_values(mut self, enabled: bool) -> Self {
+        self.config.annotations.has_values.enabled = enabled;
+        self
+    }
+    /// Enables or disables the prev annotation.
+    pub fn prev(mut self, enabled: bool) -> Self {
+        self.config.annotations.prev.enabled = enabled;
+        self
+    }
+    /// Enables or disables the next annotation.
+    pub fn next(mut self, enabled: bool) -> Self {
+        self.config.annotations.next.enabled = enabled;
+        self
+    }
+    /// Enables or disables the joiner annotation.
+    pub fn joiner(mut self, enabled: bool) -> Self {
+        self.config.annotations.joiner.enabled = enabled;
+        self
+    }
+    /// Sets the name of the count annotation.
+    pub fn count_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_count_annotation(name);
+        self
+    }
+    /// Sets the name of the first annotation.
+    pub fn first_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_first_annotation(name);
+        self
+    }
+    /// Sets the name of the last annotation.
+    pub fn last_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_last_annotation(name);
+        self
+    }
+    /// Sets the name of the index annotation.
+    pub fn index_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_index_annotation(name);
+        self
+    }
+    /// Sets the name of the odd annotation.
+    pub fn odd_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_odd_annotation(name);
+        self
+    }
+    /// Sets the name of the even annotation.
+    pub fn even_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_even_annotation(name);
+        self
+    }
+    /// Sets the name of the index1 annotation.
+    pub fn index1_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_index1_annotation(name);
+        self
+    }
+    /// Sets the name of the rindex annotation.
+    pub fn rindex_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_rindex_annotation(name);
+        self
+    }
+    /// Sets the name of the only annotation.
+    pub fn only_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_only_annotation(name);
+        self
+    }
+    /// Sets the name of the middle annotation.
+    pub fn middle_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_middle_annotation(name);
+        self
+    }
+    /// Sets the name of the empty annotation.
+    pub fn empty_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_empty_annotation(name);
+        self
+    }
+    /// Sets the name of the has_values annotation.
+    pub fn has_values_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_has_values_annotation(name);
+        self
+    }
+    /// Sets the name of the prev annotation.
+    pub fn prev_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_prev_annotation(name);
+        self
+    }
+    /// Sets the name of the next annotation.
+    pub fn next_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_next_annotation(name);
+        self
+    }
+    /// Sets the name of the joiner annotation.
+    pub fn joiner_annotation(mut self, name: &str) -> Self {
+        self.config.annotations.set_joiner_annotation(name);
+        self
+    }
+    /// Sets the value of the joiner annotation and enables it.
+    pub fn joiner_value(mut self, value: Value) -> Self {
+        self.config.annotations.enable_joiner();
+        self.config.annotations.set_joiner_value(value);
+        self
+    }
+    /// Sets the fields of object neighbours to keep in the prev and next annotations.
+    pub fn neighbour_fields(mut self, fields: Vec<String>) -> Self {
+        self.config.annotations.neighbour_fields = fields;
+        self
+    }
+    /// Sets the number of levels of nested arrays and objects to keep in the
+    /// prev and next annotations.
+    pub fn neighbour_depth(mut self, depth: usize) -> Self {
+        self.config.annotations.neighbour_depth = Some(depth);
+        self
+    }
+    /// Adds a cycle annotation, replacing any existing cycle with the same name.
+    pub fn cycle(mut self, name: &str, values: Vec<Value>) -> Self {
+        self.config.annotations.add_cycle(name, values);
+        self
+    }
+    /// Sets the key used to hold the original value of a wrapped item.
+    pub fn value_key(mut self, name: &str) -> Self {
+        self.config.annotations.set_value_key(name);
+        self
+    }
+    /// Sets the key used to hold the items of an array wrapped with a count.
+    pub fn values_key(mut self, name: &str) -> Self {
+        self.config.annotations.set_values_key(name);
+        self
+    }
+    /// Sets the key used to hold the key of an object entry.
+    pub fn entry_key(mut self, name: &str) -> Self {
+        self.config.annotations.set_entry_key(name);
+        self
+    }
+    /// Sets the objects to convert into an annotated array of entries.
+    pub fn entries(mut self, entries: Entries) -> Self {
+        self.config.entries = entries;
+        self
+    }
+    /// Sets whether entries are sorted by key rather than kept in document order.
+    pub fn sort_entries(mut self, sort_entries: bool) -> Self {
+        self.config.sort_entries = sort_entries;
+        self
+    }
+    /// Sets the policy for annotations that collide with existing keys.
+    pub fn collision_policy(mut self, collision_policy: CollisionPolicy) -> Self {
+        self.config.collision_policy = collision_policy;
+        self
+    }
+    /// Adds a rule that applies its own annotation settings to matching locations.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.config.rules.push(rule);
+        self
+    }
+    /// Sets the patterns for the locations to annotate.
+    pub fn include(mut self, include: Vec<PathPattern>) -> Self {
+        self.config.include = include;
+        self
+    }
+    /// Sets the patterns for the locations to leave untouched.
+    pub fn exclude(mut self, exclude: Vec<PathPattern>) -> Self {
+        self.config.exclude = exclude;
+        self
+    }
+    /// Sets the maximum nesting depth of arrays and objects.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Validates and returns the configuration.
+    ///
+    /// returns: Result<Config, Error>
+    pub fn build(self) -> Result<Config, Error> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    /// Validates the configuration and creates an expander that uses it.
+    ///
+    /// returns: Result<JsonExpander, Error>
+    pub fn build_expander(self) -> Result<JsonExpander, Error> {
+        Ok(JsonExpander::new(self.build()?))
+    }
+}
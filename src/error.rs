@@ -0,0 +1,235 @@
+use crate::path::Pointer;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The errors that can occur when reading, expanding or rendering JSON.
+///
+/// Errors from expansion itself carry the location where expansion failed,
+/// available through [`Error::path`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+    /// A document could not be parsed, or could not be converted to the
+    /// requested format.
+    Parse(Box<dyn std::error::Error + Send + Sync>),
+    /// The configuration or the combination of options is invalid.
+    Config(String),
+    /// An annotation collided with an existing key.
+    Collision(CollisionError),
+    /// A value was nested deeper than the configured limit.
+    Depth(DepthError),
+    /// A template could not be loaded or rendered.
+    Render(RenderError),
+}
+
+impl Error {
+    /// Creates a parse error from any error or message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let error = jxpand::Error::parse("expected a table");
+    /// assert_eq!(error.to_string(), "expected a table");
+    /// ```
+    pub fn parse(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::Parse(error.into())
+    }
+
+    /// Creates a configuration error with the given message.
+    pub fn config(message: impl ToString) -> Self {
+        Error::Config(message.to_string())
+    }
+
+    /// Gets the location within the document where expansion failed, if the
+    /// error came from expansion.
+    pub fn path(&self) -> Option<&Pointer> {
+        match self {
+            Error::Collision(e) => Some(e.path()),
+            Error::Depth(e) => Some(e.path()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Config(message) => write!(f, "{}", message),
+            Error::Collision(e) => write!(f, "{}", e),
+            Error::Depth(e) => write!(f, "{}", e),
+            Error::Render(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => e.source(),
+            Error::Parse(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        match error.classify() {
+            serde_json::error::Category::Io => Error::Io(error.into()),
+            _ => Error::Parse(Box::new(error)),
+        }
+    }
+}
+
+impl From<CollisionError> for Error {
+    fn from(error: CollisionError) -> Self {
+        Error::Collision(error)
+    }
+}
+
+impl From<DepthError> for Error {
+    fn from(error: DepthError) -> Self {
+        Error::Depth(error)
+    }
+}
+
+impl From<RenderError> for Error {
+    fn from(error: RenderError) -> Self {
+        Error::Render(error)
+    }
+}
+
+/// An error returned when an annotation collides with an existing key and the
+/// collision policy is [`CollisionPolicy::Error`](crate::cfg::CollisionPolicy::Error).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionError {
+    path: Pointer,
+}
+
+impl CollisionError {
+    /// Creates a new collision error for the key at the given location.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The location of the conflicting key, including the key itself.
+    ///
+    /// returns: CollisionError
+    pub fn new(path: Pointer) -> Self {
+        CollisionError { path }
+    }
+
+    /// Gets the location of the conflicting key.
+    pub fn path(&self) -> &Pointer {
+        &self.path
+    }
+
+    /// Gets the conflicting key.
+    pub fn key(&self) -> &str {
+        self.path.segments().last().map_or("", String::as_str)
+    }
+}
+
+impl Display for CollisionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "annotation '{}' collides with an existing key at '{}'",
+            self.key(),
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for CollisionError {}
+
+/// An error returned when an array or object is nested deeper than the
+/// configured [maximum depth](crate::cfg::Config::max_depth).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthError {
+    path: Pointer,
+    limit: usize,
+}
+
+impl DepthError {
+    /// Creates a new depth error for the value at the given location.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The location of the value that exceeds the limit.
+    /// * `limit`: The maximum depth that was exceeded.
+    ///
+    /// returns: DepthError
+    pub fn new(path: Pointer, limit: usize) -> Self {
+        DepthError { path, limit }
+    }
+
+    /// Gets the location of the value that exceeds the limit.
+    pub fn path(&self) -> &Pointer {
+        &self.path
+    }
+
+    /// Gets the maximum depth that was exceeded.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl Display for DepthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "value at '{}' is nested deeper than the maximum depth of {}",
+            self.path, self.limit
+        )
+    }
+}
+
+impl std::error::Error for DepthError {}
+
+/// An error returned when a template cannot be loaded or rendered.
+#[derive(Debug)]
+pub struct RenderError {
+    template: PathBuf,
+    message: String,
+}
+
+impl RenderError {
+    /// Creates a new render error for the given template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template`: The path of the template that failed.
+    /// * `message`: A description of the failure.
+    ///
+    /// returns: RenderError
+    pub fn new(template: &Path, message: impl ToString) -> Self {
+        RenderError {
+            template: template.to_path_buf(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Gets the path of the template that failed.
+    pub fn template(&self) -> &Path {
+        &self.template
+    }
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.template.display(), self.message)
+    }
+}
+
+impl std::error::Error for RenderError {}
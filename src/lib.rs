@@ -1,7 +1,18 @@
-use crate::cfg::{AnnotationMode, Config};
+use crate::cfg::{AnnotationMode, Annotations, CollisionPolicy, Config, Entries};
+use crate::error::{CollisionError, DepthError};
+use crate::path::Pointer;
+use crate::provider::{AnnotationProvider, ItemContext};
+use serde_json::{Map, Value};
 use std::fmt::Debug;
 
 pub mod cfg;
+pub mod error;
+pub mod path;
+pub mod provider;
+#[cfg(feature = "render")]
+pub mod render;
+
+pub use error::Error;
 
 pub trait Expander<T> {
     /// Expands a value, applying annotations as configured.
@@ -12,11 +23,35 @@ pub trait Expander<T> {
     ///
     /// returns: T
     fn expand(&self, value: T) -> T;
+
+    /// Expands a value, applying annotations as configured, and returns an
+    /// error if expansion fails.
+    ///
+    /// Expanders that cannot fail can rely on the default, which wraps
+    /// [`Expander::expand`].
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: The value to expand.
+    ///
+    /// returns: Result<T, Error>
+    fn try_expand(&self, value: T) -> Result<T, Error> {
+        Ok(self.expand(value))
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct JsonExpander {
     config: Config,
+    providers: Vec<Box<dyn AnnotationProvider>>,
+}
+
+/// The settings that apply to a particular array or entries.
+struct Scope<'a> {
+    annotations: &'a Annotations,
+    object_mode: &'a AnnotationMode,
+    /// The prefix for annotations from custom providers, empty in wrap mode.
+    prefix: &'a str,
 }
 
 impl JsonExpander {
@@ -37,88 +72,448 @@ impl JsonExpander {
     pub fn new(config: Config) -> Self {
         JsonExpander {
             config: config.resolve(),
+            providers: provider::builtin(),
         }
     }
 
+    /// Registers a provider that adds its own annotations to every item,
+    /// after the built-in annotations.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider`: The provider to add.
+    ///
+    /// returns: JsonExpander
+    pub fn with_provider(mut self, provider: impl AnnotationProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Creates a builder for the configuration of an expander.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let expander = jxpand::JsonExpander::builder()
+    ///     .count(false)
+    ///     .build_expander()
+    ///     .unwrap();
+    /// assert!(!expander.config().annotations().count().is_enabled());
+    /// ```
+    pub fn builder() -> cfg::ConfigBuilder {
+        Config::builder()
+    }
+
     /// Gets the configuration used by the expander.
     pub fn config(&self) -> &Config {
         &self.config
     }
+
+    /// Gets the registered annotation providers, including the built-ins.
+    pub fn providers(&self) -> &[Box<dyn AnnotationProvider>] {
+        &self.providers
+    }
 }
 
-impl Expander<serde_json::Value> for JsonExpander {
-    fn expand(&self, value: serde_json::Value) -> serde_json::Value {
-        use serde_json::Value;
-        let annotations = self.config.annotations();
-        if annotations.none() {
-            return value;
+impl Default for JsonExpander {
+    fn default() -> Self {
+        JsonExpander::new(Config::default())
+    }
+}
+
+impl JsonExpander {
+    /// Expands a value as the item at `index` of a top-level array whose length
+    /// may not be known, such as a stream of documents.
+    ///
+    /// The item is annotated as it would be within the array, but the count
+    /// annotation is not applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: The item to expand.
+    /// * `index`: The index of the item.
+    /// * `last`: Whether this is the last item.
+    ///
+    /// returns: Result<Value, Error>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// let expander = jxpand::JsonExpander::default();
+    /// let item = expander.try_expand_item(json!("b"), 1, true).unwrap();
+    /// assert_eq!(item, json!({"value": "b", "index": 1, "first": false, "last": true}));
+    /// ```
+    pub fn try_expand_item(&self, value: Value, index: usize, last: bool) -> Result<Value, Error> {
+        let path = Pointer::root().child(index);
+        let Some(scope) = self.scope(&Pointer::root()) else {
+            return self.expand_at(value, &path);
+        };
+        if !self.items_enabled(scope.annotations) {
+            return self.expand_at(value, &path);
+        }
+        let (value, merge) = self.expand_content(scope.object_mode, value, &path)?;
+        let item = ItemContext::new(
+            index,
+            last,
+            &value,
+            None,
+            &path,
+            None,
+            scope.annotations,
+            scope.prefix,
+        );
+        let annotations = self.annotate(&item, &[]);
+        self.wrap_item(&scope, value, merge, &path, annotations)
+    }
+
+    /// Gets the settings that apply to the array or entries at the given
+    /// location, or `None` if it should not be annotated.
+    fn scope(&self, path: &Pointer) -> Option<Scope<'_>> {
+        if !self.config.is_included(path) {
+            return None;
+        }
+        let (annotations, object_mode, prefix) = match self.config.rule_for(path) {
+            Some(rule) => (
+                rule.annotations(),
+                rule.object_mode(),
+                rule.annotation_prefix(),
+            ),
+            None => (
+                self.config.annotations(),
+                self.config.object_mode(),
+                self.config.annotation_prefix(),
+            ),
+        };
+        let prefix = match object_mode {
+            AnnotationMode::Wrap => "",
+            AnnotationMode::Merge => prefix,
+        };
+        Some(Scope {
+            annotations,
+            object_mode,
+            prefix,
+        })
+    }
+
+    /// Returns whether any provider adds annotations to items with the given settings.
+    fn items_enabled(&self, annotations: &Annotations) -> bool {
+        self.providers.iter().any(|p| p.is_enabled(annotations))
+    }
+
+    /// Keeps what each enabled provider needs of the items before they are
+    /// expanded, indexed by provider.
+    fn originals(&self, annotations: &Annotations, values: &[Value]) -> Vec<Option<Vec<Value>>> {
+        self.providers
+            .iter()
+            .map(|p| match p.is_enabled(annotations) {
+                true => values.iter().map(|v| p.original(annotations, v)).collect(),
+                false => None,
+            })
+            .collect()
+    }
+
+    /// Fails if the array or object at the given location is nested deeper
+    /// than the maximum depth.
+    fn check_depth(&self, path: &Pointer) -> Result<(), DepthError> {
+        match self.config.max_depth() {
+            Some(limit) if path.segments().len() >= limit => {
+                Err(DepthError::new(path.clone(), limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn expand_at(&self, value: Value, path: &Pointer) -> Result<Value, Error> {
+        if self.config.is_excluded(path) {
+            return Ok(value);
         }
+        match value {
+            Value::Object(map) if self.config.entries().matches(path) => match self.scope(path) {
+                Some(scope) => self.expand_entries(map, path, &scope),
+                None => Ok(Value::Object(self.expand_members(map, path)?)),
+            },
+            Value::Object(map) => Ok(Value::Object(self.expand_members(map, path)?)),
+            Value::Array(values) => self.expand_array(values, path),
+            _ => Ok(value),
+        }
+    }
+
+    fn expand_members(
+        &self,
+        map: Map<String, Value>,
+        path: &Pointer,
+    ) -> Result<Map<String, Value>, Error> {
+        self.check_depth(path)?;
+        map.into_iter()
+            .map(|(k, v)| {
+                let v = self.expand_at(v, &path.child(&k))?;
+                Ok((k, v))
+            })
+            .collect()
+    }
 
-        let object_mode = self.config.object_mode();
+    fn expand_array(&self, values: Vec<Value>, path: &Pointer) -> Result<Value, Error> {
+        self.check_depth(path)?;
+        let scope = match self.scope(path) {
+            Some(scope) if self.items_enabled(scope.annotations) => scope,
+            scope => {
+                let values = values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| self.expand_at(v, &path.child(i)))
+                    .collect::<Result<_, _>>()?;
+                return Ok(match scope {
+                    Some(scope) => self.wrap_count(scope.annotations, values),
+                    None => Value::Array(values),
+                });
+            }
+        };
 
+        let paths: Vec<_> = (0..values.len()).map(|i| path.child(i)).collect();
+        let originals = self.originals(scope.annotations, &values);
+        let mut merge = Vec::with_capacity(values.len());
+        let mut expanded = Vec::with_capacity(values.len());
+        for (value, path) in values.into_iter().zip(&paths) {
+            let (value, merged) = self.expand_content(scope.object_mode, value, path)?;
+            expanded.push(value);
+            merge.push(merged);
+        }
+
+        let annotations = self.annotate_all(&scope, &expanded, &originals, None, &paths);
+        let items = expanded
+            .into_iter()
+            .zip(merge)
+            .zip(paths.iter().zip(annotations))
+            .map(|((value, merge), (path, annotations))| {
+                self.wrap_item(&scope, value, merge, path, annotations)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self.wrap_count(scope.annotations, items))
+    }
+
+    /// Expands the contents of an item, returning whether the annotations
+    /// should be merged into the expanded value rather than wrapping it.
+    fn expand_content(
+        &self,
+        object_mode: &AnnotationMode,
+        value: Value,
+        path: &Pointer,
+    ) -> Result<(Value, bool), Error> {
+        // Objects converted into entries become arrays, so they are wrapped.
+        let merge =
+            matches!(object_mode, AnnotationMode::Merge) && !self.config.entries().matches(path);
         match value {
-            Value::Object(map) => {
-                let mut new_map = serde_json::Map::new();
-                for (k, v) in map {
-                    new_map.insert(k, self.expand(v));
-                }
-                Value::Object(new_map)
+            Value::Object(map) if merge => {
+                Ok((Value::Object(self.expand_members(map, path)?), true))
             }
-            Value::Array(values) => {
-                let count = values.len();
-                let mut new_values = Vec::new();
-                for (i, v) in values.into_iter().enumerate() {
-                    let mut wrapper = match v {
-                        Value::Object(map) => match object_mode {
-                            AnnotationMode::Wrap => {
-                                let mut wrapper = serde_json::Map::new();
-                                wrapper.insert("value".to_string(), Value::Object(map));
-                                wrapper
-                            }
-                            AnnotationMode::Merge => {
-                                let mut new_map = serde_json::Map::new();
-                                for (k, v) in map {
-                                    new_map.insert(k, self.expand(v));
-                                }
-                                new_map
-                            }
-                        },
-                        _ => {
-                            let mut wrapper = serde_json::Map::new();
-                            wrapper.insert("value".to_string(), self.expand(v));
-                            wrapper
-                        }
-                    };
-
-                    if annotations.index().is_enabled() {
-                        wrapper.insert(annotations.index().annotation(), Value::Number(i.into()));
-                    }
-                    if annotations.first().is_enabled() {
-                        wrapper.insert(annotations.first().annotation(), Value::Bool(i == 0));
-                    }
-                    if annotations.last().is_enabled() {
-                        wrapper
-                            .insert(annotations.last().annotation(), Value::Bool(i == count - 1));
-                    }
+            value => Ok((self.expand_at(value, path)?, false)),
+        }
+    }
 
-                    new_values.push(Value::Object(wrapper));
-                }
-                if annotations.count().is_enabled() {
-                    let mut wrapper = serde_json::Map::new();
-                    wrapper.insert("values".to_string(), Value::Array(new_values));
-                    if annotations.count().is_enabled() {
-                        wrapper.insert(
-                            annotations.count().annotation(),
-                            Value::Number(count.into()),
-                        );
-                    }
-                    Value::Object(wrapper)
-                } else {
-                    Value::Array(new_values)
-                }
+    /// Adds annotations to an expanded item, wrapping it unless its
+    /// annotations are merged into it.
+    fn wrap_item(
+        &self,
+        scope: &Scope,
+        value: Value,
+        merge: bool,
+        path: &Pointer,
+        annotations: Vec<(String, Value)>,
+    ) -> Result<Value, Error> {
+        let mut wrapper = match value {
+            Value::Object(map) if merge => map,
+            value => {
+                let mut wrapper = Map::new();
+                wrapper.insert(scope.annotations.value_key().to_string(), value);
+                wrapper
             }
-            _ => value,
+        };
+        for (key, value) in annotations {
+            self.insert(&mut wrapper, path, key, value)?;
         }
+        Ok(Value::Object(wrapper))
+    }
+
+    fn expand_entries(
+        &self,
+        map: Map<String, Value>,
+        path: &Pointer,
+        scope: &Scope,
+    ) -> Result<Value, Error> {
+        self.check_depth(path)?;
+        let mut members: Vec<_> = map.into_iter().collect();
+        if self.config.sort_entries() {
+            members.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        let (keys, values): (Vec<_>, Vec<_>) = members.into_iter().unzip();
+        let paths: Vec<_> = keys.iter().map(|k| path.child(k)).collect();
+        let originals = self.originals(scope.annotations, &values);
+        let expanded = values
+            .into_iter()
+            .zip(&paths)
+            .map(|(v, path)| self.expand_at(v, path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let annotations = self.annotate_all(scope, &expanded, &originals, Some(&keys), &paths);
+        let mut entries = Vec::with_capacity(keys.len());
+        let members = keys.into_iter().zip(expanded);
+        for ((key, value), (path, annotations)) in members.zip(paths.iter().zip(annotations)) {
+            let mut entry = Map::new();
+            entry.insert(
+                scope.annotations.entry_key().to_string(),
+                Value::String(key),
+            );
+            entry.insert(scope.annotations.value_key().to_string(), value);
+            for (key, value) in annotations {
+                self.insert(&mut entry, path, key, value)?;
+            }
+            entries.push(Value::Object(entry));
+        }
+        Ok(self.wrap_count(scope.annotations, entries))
+    }
+
+    /// Collects the annotations for every item of an array or entries.
+    fn annotate_all(
+        &self,
+        scope: &Scope,
+        values: &[Value],
+        originals: &[Option<Vec<Value>>],
+        keys: Option<&[String]>,
+        paths: &[Pointer],
+    ) -> Vec<Vec<(String, Value)>> {
+        (0..values.len())
+            .map(|i| {
+                let item = ItemContext::new(
+                    i,
+                    i == values.len() - 1,
+                    &values[i],
+                    keys.map(|keys| keys[i].as_str()),
+                    &paths[i],
+                    Some(values),
+                    scope.annotations,
+                    scope.prefix,
+                );
+                self.annotate(&item, originals)
+            })
+            .collect()
+    }
+
+    /// Collects the annotations for an item from the enabled providers,
+    /// giving each the values it kept of the items before they were expanded.
+    fn annotate(
+        &self,
+        item: &ItemContext,
+        originals: &[Option<Vec<Value>>],
+    ) -> Vec<(String, Value)> {
+        self.providers
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_enabled(item.annotations()))
+            .flat_map(|(i, p)| {
+                let originals = originals.get(i).and_then(Option::as_deref);
+                p.annotate(&item.with_originals(originals))
+            })
+            .collect()
+    }
+
+    /// Inserts an annotation, applying the collision policy if the key is already present.
+    fn insert(
+        &self,
+        wrapper: &mut Map<String, Value>,
+        path: &Pointer,
+        key: String,
+        value: Value,
+    ) -> Result<(), CollisionError> {
+        if !wrapper.contains_key(&key) {
+            wrapper.insert(key, value);
+            return Ok(());
+        }
+        match self.config.collision_policy() {
+            CollisionPolicy::Error => return Err(CollisionError::new(path.child(key))),
+            CollisionPolicy::Skip => {}
+            CollisionPolicy::Overwrite => {
+                wrapper.insert(key, value);
+            }
+            CollisionPolicy::Suffix => {
+                let key = (1..)
+                    .map(|n| format!("{}_{}", key, n))
+                    .find(|k| !wrapper.contains_key(k))
+                    .expect("a free suffix always exists");
+                wrapper.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Wraps the items of an array or entries in an object holding the
+    /// array-level annotations, if any are enabled.
+    fn wrap_count(&self, annotations: &Annotations, values: Vec<Value>) -> Value {
+        if !annotations.wraps_arrays() {
+            return Value::Array(values);
+        }
+        let count = values.len();
+        let mut wrapper = Map::new();
+        wrapper.insert(annotations.values_key().to_string(), Value::Array(values));
+        if annotations.count().is_enabled() {
+            let key = annotations.count().annotation();
+            wrapper.insert(key, Value::Number(count.into()));
+        }
+        if annotations.empty().is_enabled() {
+            wrapper.insert(annotations.empty().annotation(), Value::Bool(count == 0));
+        }
+        if annotations.has_values().is_enabled() {
+            let key = annotations.has_values().annotation();
+            wrapper.insert(key, Value::Bool(count > 0));
+        }
+        Value::Object(wrapper)
+    }
+}
+
+impl Expander<Value> for JsonExpander {
+    /// Expands a value, applying annotations as configured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an annotation collides with an existing key and the collision
+    /// policy is [`CollisionPolicy::Error`], or if the value is nested deeper
+    /// than the maximum depth. Use [`Expander::try_expand`] to handle these
+    /// failures as errors.
+    fn expand(&self, value: Value) -> Value {
+        match self.try_expand(value) {
+            Ok(value) => value,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Expands a value, returning an error if an annotation collides with an
+    /// existing key and the collision policy is [`CollisionPolicy::Error`], or
+    /// if the value is nested deeper than the maximum depth.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::cfg::{AnnotationMode, Annotations, CollisionPolicy, Config};
+    /// use jxpand::Expander;
+    /// let mut config = Config::new(Annotations::default(), "_".to_string(), AnnotationMode::Merge);
+    /// config.set_collision_policy(CollisionPolicy::Error);
+    /// let expander = jxpand::JsonExpander::new(config);
+    /// let error = expander.try_expand(serde_json::json!([{"_index": 7}])).unwrap_err();
+    /// assert_eq!(error.path().unwrap().to_string(), "/0/_index");
+    /// ```
+    fn try_expand(&self, value: Value) -> Result<Value, Error> {
+        let config = &self.config;
+        let none = |annotations: &Annotations| {
+            !annotations.wraps_arrays() && !self.items_enabled(annotations)
+        };
+        if none(config.annotations())
+            && matches!(config.entries(), Entries::None)
+            && config.rules().iter().all(|rule| none(rule.annotations()))
+            && config.max_depth().is_none()
+        {
+            return Ok(value);
+        }
+        self.expand_at(value, &Pointer::root())
     }
 }
 
@@ -136,7 +531,7 @@ impl Expander<serde_json::Value> for JsonExpander {
 /// let value = serde_json::json!([1, 2, 3]);
 /// let result = jxpand::expand_json(value);
 /// ```
-pub fn expand_json(value: serde_json::Value) -> serde_json::Value {
+pub fn expand_json(value: Value) -> Value {
     let expander = JsonExpander::default();
     expander.expand(value)
 }
@@ -183,4 +578,674 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_expand_nested_arrays_in_wrapped_objects() {
+        let value = json!([{"name": "a", "fields": [{"tags": ["x"]}]}]);
+        let result = expand_json(value);
+        assert_eq!(
+            result,
+            json!({
+                "values": [{
+                    "index": 0, "first": true, "last": true,
+                    "value": {
+                        "name": "a",
+                        "fields": {
+                            "values": [{
+                                "index": 0, "first": true, "last": true,
+                                "value": {
+                                    "tags": {
+                                        "values": [
+                                            {"index": 0, "first": true, "last": true, "value": "x"},
+                                        ],
+                                        "count": 1,
+                                    },
+                                },
+                            }],
+                            "count": 1,
+                        },
+                    },
+                }],
+                "count": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_nested_arrays_in_merged_objects() {
+        let config = Config::new(
+            cfg::Annotations::default(),
+            "_".to_string(),
+            AnnotationMode::Merge,
+        );
+        let expander = JsonExpander::new(config);
+        let value = json!([{"name": "a", "fields": [{"tags": ["x"]}]}]);
+        let result = expander.expand(value);
+        assert_eq!(
+            result,
+            json!({
+                "_values": [{
+                    "_index": 0, "_first": true, "_last": true,
+                    "name": "a",
+                    "fields": {
+                        "_values": [{
+                            "_index": 0, "_first": true, "_last": true,
+                            "tags": {
+                                "_values": [
+                                    {"_index": 0, "_first": true, "_last": true, "_value": "x"},
+                                ],
+                                "_count": 1,
+                            },
+                        }],
+                        "_count": 1,
+                    },
+                }],
+                "_count": 1,
+            })
+        );
+    }
+
+    fn merge_with_policy(policy: CollisionPolicy) -> JsonExpander {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_count();
+        annotations.disable_last();
+        let mut config = Config::new(annotations, "_".to_string(), AnnotationMode::Merge);
+        config.set_collision_policy(policy);
+        JsonExpander::new(config)
+    }
+
+    #[test]
+    fn test_merge_collision_policies() {
+        let value = json!([{"_index": "mine", "name": "a"}]);
+
+        let result = merge_with_policy(CollisionPolicy::Overwrite).try_expand(value.clone());
+        assert_eq!(
+            result.unwrap(),
+            json!([{"_index": 0, "name": "a", "_first": true}])
+        );
+
+        let result = merge_with_policy(CollisionPolicy::Skip).try_expand(value.clone());
+        assert_eq!(
+            result.unwrap(),
+            json!([{"_index": "mine", "name": "a", "_first": true}])
+        );
+
+        let result = merge_with_policy(CollisionPolicy::Suffix).try_expand(value.clone());
+        assert_eq!(
+            result.unwrap(),
+            json!([{"_index": "mine", "name": "a", "_index_1": 0, "_first": true}])
+        );
+    }
+
+    #[test]
+    fn test_merge_collision_error_names_path() {
+        let value = json!({"items": [{"name": "a"}, {"name": "b", "_first": false}]});
+        let error = merge_with_policy(CollisionPolicy::Error)
+            .try_expand(value)
+            .unwrap_err();
+        let Error::Collision(error) = error else {
+            panic!("expected a collision error, found {:?}", error);
+        };
+        assert_eq!(error.path().to_string(), "/items/1/_first");
+        assert_eq!(error.key(), "_first");
+    }
+
+    #[test]
+    fn test_builder_validates_rules() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.set_entry_key("first");
+        let rule = cfg::Rule::new(
+            "/items".parse().unwrap(),
+            annotations,
+            "_".to_string(),
+            AnnotationMode::Merge,
+        );
+        let error = JsonExpander::builder().rule(rule).build().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "rule for '/items': '_first' is used by more than one annotation"
+        );
+
+        let error = JsonExpander::builder().count_annotation("").build();
+        assert!(matches!(error, Err(Error::Config(_))));
+
+        let config = JsonExpander::builder()
+            .first(false)
+            .first_annotation("index")
+            .build();
+        assert!(config.is_ok());
+    }
+
+    #[derive(Debug)]
+    struct Neighbours;
+
+    impl AnnotationProvider for Neighbours {
+        fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+            let parent = item.parent().unwrap();
+            vec![
+                (item.annotation("key"), json!(item.key())),
+                (item.annotation("path"), json!(item.path().to_string())),
+                (item.annotation("siblings"), json!(parent.len() - 1)),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_expand_with_custom_provider() {
+        let expander = JsonExpander::builder()
+            .object_mode(AnnotationMode::Merge)
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .build_expander()
+            .unwrap()
+            .with_provider(Neighbours);
+        let result = expander.expand(json!({"items": [{"a": 1}, 2]}));
+        assert_eq!(
+            result,
+            json!({"items": [
+                {"a": 1, "_key": null, "_path": "/items/0", "_siblings": 1},
+                {"_value": 2, "_key": null, "_path": "/items/1", "_siblings": 1},
+            ]})
+        );
+
+        let mut config = Config::default();
+        config.set_entries(Entries::All);
+        config.annotations_mut().disable();
+        let expander = JsonExpander::new(config).with_provider(Neighbours);
+        let result = expander.expand(json!({"b": true}));
+        assert_eq!(
+            result,
+            json!([{"key": "b", "value": true, "path": "/b", "siblings": 0}])
+        );
+    }
+
+    struct Upper;
+
+    impl Expander<String> for Upper {
+        fn expand(&self, value: String) -> String {
+            value.to_uppercase()
+        }
+    }
+
+    #[test]
+    fn test_try_expand_defaults_to_expand() {
+        assert_eq!(Upper.try_expand("abc".to_string()).unwrap(), "ABC");
+    }
+
+    #[test]
+    fn test_max_depth_error_names_path() {
+        let mut config = Config::default();
+        config.set_max_depth(Some(2));
+        let expander = JsonExpander::new(config);
+        assert!(expander.try_expand(json!({"a": [1, 2]})).is_ok());
+
+        let error = expander
+            .try_expand(json!({"a": [1, {"b": true}]}))
+            .unwrap_err();
+        assert!(matches!(error, Error::Depth(_)), "{:?}", error);
+        assert_eq!(error.path().unwrap().to_string(), "/a/1");
+    }
+
+    #[test]
+    fn test_expand_array_annotation_combinations() {
+        for flags in 0..16 {
+            let (count, first, last, index) = (
+                flags & 1 != 0,
+                flags & 2 != 0,
+                flags & 4 != 0,
+                flags & 8 != 0,
+            );
+            let mut annotations = cfg::Annotations::default();
+            annotations.disable();
+            if count {
+                annotations.enable_count();
+            }
+            if first {
+                annotations.enable_first();
+            }
+            if last {
+                annotations.enable_last();
+            }
+            if index {
+                annotations.enable_index();
+            }
+            let config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+            let expander = JsonExpander::new(config);
+
+            let items: Vec<Value> = ["a", "b"]
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    if !first && !last && !index {
+                        return json!(v);
+                    }
+                    let mut item = json!({"value": v});
+                    if first {
+                        item["first"] = json!(i == 0);
+                    }
+                    if last {
+                        item["last"] = json!(i == 1);
+                    }
+                    if index {
+                        item["index"] = json!(i);
+                    }
+                    item
+                })
+                .collect();
+            let expected = if count {
+                json!({"values": items, "count": 2})
+            } else {
+                json!(items)
+            };
+
+            let result = expander.expand(json!(["a", "b"]));
+            assert_eq!(
+                result, expected,
+                "count={} first={} last={} index={}",
+                count, first, last, index
+            );
+        }
+    }
+
+    #[test]
+    fn test_expand_items_unwrapped_when_only_count_enabled() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_first();
+        annotations.disable_last();
+        annotations.disable_index();
+        let config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!([{"tags": ["x", "y"]}, [1]]));
+        assert_eq!(
+            result,
+            json!({
+                "values": [
+                    {"tags": {"values": ["x", "y"], "count": 2}},
+                    {"values": [1], "count": 1},
+                ],
+                "count": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_with_custom_wrapper_keys() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_first();
+        annotations.disable_last();
+        annotations.set_value_key("item");
+        annotations.set_values_key("items");
+        let config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!({"values": ["a"]}));
+        assert_eq!(
+            result,
+            json!({"values": {"items": [{"item": "a", "index": 0}], "count": 1}})
+        );
+    }
+
+    // Without preserve_order, objects forget the document order of their keys.
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn test_expand_all_objects_to_entries() {
+        let mut config = Config::default();
+        config.set_entries(Entries::All);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!({"b": 1, "a": {"c": true}}));
+        assert_eq!(
+            result,
+            json!({
+                "values": [
+                    {"key": "b", "value": 1, "index": 0, "first": true, "last": false},
+                    {
+                        "key": "a",
+                        "value": {
+                            "values": [
+                                {"key": "c", "value": true, "index": 0, "first": true, "last": true},
+                            ],
+                            "count": 1,
+                        },
+                        "index": 1,
+                        "first": false,
+                        "last": true,
+                    },
+                ],
+                "count": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_merged_items_to_entries() {
+        let config = JsonExpander::builder()
+            .object_mode(AnnotationMode::Merge)
+            .entries(Entries::All)
+            .count(false)
+            .last(false)
+            .build()
+            .unwrap();
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!([{"a": 1}, 2]));
+        assert_eq!(
+            result,
+            json!([
+                {
+                    "_value": [{"_key": "a", "_value": 1, "_index": 0, "_first": true}],
+                    "_index": 0,
+                    "_first": true,
+                },
+                {"_value": 2, "_index": 1, "_first": false},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_entries_at_path_sorted() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_count();
+        annotations.disable_index();
+        let mut config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+        config.set_entries(Entries::At(vec!["/enums/0/variants".parse().unwrap()]));
+        config.set_sort_entries(true);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!({
+            "enums": [{"variants": {"Red": 1, "Blue": 2}, "other": {"x": 1}}],
+        }));
+        let keys: Vec<_> = result["enums"][0]["value"]["variants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["key"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec!["Blue", "Red"]);
+        assert_eq!(
+            result["enums"][0]["value"]["variants"][0],
+            json!({"key": "Blue", "value": 2, "first": true, "last": false})
+        );
+        assert_eq!(result["enums"][0]["value"]["other"], json!({"x": 1}));
+    }
+
+    #[test]
+    fn test_expand_with_rules_include_and_exclude() {
+        let mut config = Config::default();
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_count();
+        annotations.disable_first();
+        annotations.disable_last();
+        config.add_rule(cfg::Rule::new(
+            "/services/*/ports".parse().unwrap(),
+            annotations,
+            "_".to_string(),
+            AnnotationMode::Wrap,
+        ));
+        config.add_rule(cfg::Rule::new(
+            "/services/db/ports".parse().unwrap(),
+            cfg::Annotations::default(),
+            "$".to_string(),
+            AnnotationMode::Merge,
+        ));
+        config.set_include(vec!["$.services.*.*".parse().unwrap()]);
+        config.set_exclude(vec!["/services/*/raw".parse().unwrap()]);
+        let expander = JsonExpander::new(config);
+
+        let result = expander.expand(json!({
+            "services": {
+                "web": {"ports": [80], "env": ["A"], "raw": [[1]]},
+                "db": {"ports": [{"port": 5432}]},
+            },
+            "other": [1],
+        }));
+        assert_eq!(
+            result,
+            json!({
+                "services": {
+                    "web": {
+                        "ports": [{"value": 80, "index": 0}],
+                        "env": {
+                            "values": [{"value": "A", "index": 0, "first": true, "last": true}],
+                            "count": 1,
+                        },
+                        "raw": [[1]],
+                    },
+                    "db": {
+                        "ports": {
+                            "$values": [{"port": 5432, "$index": 0, "$first": true, "$last": true}],
+                            "$count": 1,
+                        },
+                    },
+                },
+                "other": [1],
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_array_with_joiner() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable_count();
+        annotations.disable_first();
+        annotations.disable_last();
+        annotations.disable_index();
+        annotations.enable_joiner();
+        annotations.set_joiner_value(json!(", "));
+        let config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!(["a", "b", "c"]));
+        assert_eq!(
+            result,
+            json!([
+                {"value": "a", "joiner": ", "},
+                {"value": "b", "joiner": ", "},
+                {"value": "c", "joiner": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_array_with_parity() {
+        let config = JsonExpander::builder()
+            .object_mode(AnnotationMode::Merge)
+            .count(false)
+            .first(false)
+            .last(false)
+            .odd(true)
+            .even(true)
+            .even_annotation("stripe")
+            .build()
+            .unwrap();
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!([{"n": 1}, {"n": 2}, {"n": 3}]));
+        assert_eq!(
+            result,
+            json!([
+                {"n": 1, "_index": 0, "_odd": true, "_stripe": false},
+                {"n": 2, "_index": 1, "_odd": false, "_stripe": true},
+                {"n": 3, "_index": 2, "_odd": true, "_stripe": false},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_array_with_positions() {
+        let expander = JsonExpander::builder()
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .index1(true)
+            .rindex(true)
+            .rindex_annotation("remaining")
+            .build_expander()
+            .unwrap();
+        let result = expander.expand(json!(["a", "b", "c"]));
+        assert_eq!(
+            result,
+            json!([
+                {"value": "a", "index1": 1, "remaining": 2},
+                {"value": "b", "index1": 2, "remaining": 1},
+                {"value": "c", "index1": 3, "remaining": 0},
+            ])
+        );
+
+        let item = expander.try_expand_item(json!("x"), 4, false).unwrap();
+        assert_eq!(item, json!({"value": "x", "index1": 5, "remaining": null}));
+    }
+
+    #[test]
+    fn test_expand_array_with_only_middle_and_empty() {
+        let expander = JsonExpander::builder()
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .only(true)
+            .middle(true)
+            .empty(true)
+            .has_values(true)
+            .build_expander()
+            .unwrap();
+        let result = expander.expand(json!({"one": [1], "three": [1, 2, 3], "none": []}));
+        assert_eq!(
+            result,
+            json!({
+                "one": {
+                    "values": [{"value": 1, "only": true, "middle": false}],
+                    "empty": false,
+                    "has_values": true,
+                },
+                "three": {
+                    "values": [
+                        {"value": 1, "only": false, "middle": false},
+                        {"value": 2, "only": false, "middle": true},
+                        {"value": 3, "only": false, "middle": false},
+                    ],
+                    "empty": false,
+                    "has_values": true,
+                },
+                "none": {"values": [], "empty": true, "has_values": false},
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_array_with_neighbours() {
+        let expander = JsonExpander::builder()
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .prev(true)
+            .next(true)
+            .build_expander()
+            .unwrap();
+        let result = expander.expand(json!([1, 2, 3]));
+        assert_eq!(
+            result,
+            json!([
+                {"value": 1, "prev": null, "next": 2},
+                {"value": 2, "prev": 1, "next": 3},
+                {"value": 3, "prev": 2, "next": null},
+            ])
+        );
+
+        let item = expander.try_expand_item(json!(4), 3, false).unwrap();
+        assert_eq!(item, json!({"value": 4, "prev": null, "next": null}));
+    }
+
+    #[test]
+    fn test_expand_nested_arrays_with_neighbours() {
+        let expander = JsonExpander::builder()
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .prev(true)
+            .next(true)
+            .build_expander()
+            .unwrap();
+        let result = expander.expand(json!([[1, 2], [3]]));
+        assert_eq!(
+            result,
+            json!([
+                {
+                    "value": [
+                        {"value": 1, "prev": null, "next": 2},
+                        {"value": 2, "prev": 1, "next": null},
+                    ],
+                    "prev": null,
+                    "next": [3],
+                },
+                {
+                    "value": [{"value": 3, "prev": null, "next": null}],
+                    "prev": [1, 2],
+                    "next": null,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_array_with_projected_neighbours() {
+        let expander = JsonExpander::builder()
+            .count(false)
+            .first(false)
+            .last(false)
+            .index(false)
+            .next(true)
+            .neighbour_fields(vec!["id".to_string(), "tags".to_string()])
+            .neighbour_depth(1)
+            .build_expander()
+            .unwrap();
+        let result = expander.expand(json!([
+            {"id": 1, "name": "a"},
+            {"id": 2, "name": "b", "tags": ["x"]},
+        ]));
+        assert_eq!(
+            result,
+            json!([
+                {"value": {"id": 1, "name": "a"}, "next": {"id": 2}},
+                {
+                    "value": {"id": 2, "name": "b", "tags": [{"value": "x", "next": null}]},
+                    "next": null,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_cycle_fields() {
+        let cycles = json!({"annotations": {"cycles": [
+            {"annotation": "row", "values": ["a", "b"], "enabled": false},
+        ]}});
+        let config: Config = serde_json::from_value(cycles).unwrap();
+        assert!(!config.annotations().cycles()[0].is_enabled());
+
+        let cycles = json!({"annotations": {"cycles": [
+            {"annotation": "row", "values": ["a", "b"], "bogus": 1},
+        ]}});
+        let error = serde_json::from_value::<Config>(cycles).unwrap_err();
+        assert!(error.to_string().contains("bogus"), "{}", error);
+    }
+
+    #[test]
+    fn test_expand_array_with_cycles() {
+        let mut annotations = cfg::Annotations::default();
+        annotations.disable();
+        annotations.add_cycle("row", vec![json!("odd"), json!("even")]);
+        annotations.add_cycle("colour", vec![json!("red"), json!("green"), json!("blue")]);
+        let config = Config::new(annotations, "_".to_string(), AnnotationMode::Wrap);
+        let expander = JsonExpander::new(config);
+        let result = expander.expand(json!([1, 2, 3, 4]));
+        assert_eq!(
+            result,
+            json!([
+                {"value": 1, "row": "odd", "colour": "red"},
+                {"value": 2, "row": "even", "colour": "green"},
+                {"value": 3, "row": "odd", "colour": "blue"},
+                {"value": 4, "row": "even", "colour": "red"},
+            ])
+        );
+    }
 }
@@ -1,68 +1,792 @@
-use clap::Parser;
-use jxpand::cfg::{AnnotationMode, Annotations, Config};
-use jxpand::Expander;
-use std::error::Error;
+use clap::{Args, Parser, Subcommand};
+use jxpand::cfg::{AnnotationMode, Annotations, CollisionPolicy, Config, Entries};
+use jxpand::path::PathPattern;
+use jxpand::render::Engine;
+use jxpand::{Error, Expander, JsonExpander};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The configuration file that is used if present in the working directory.
+const DEFAULT_CONFIG_FILE: &str = "jxpand.toml";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Pretty print the JSON output
     #[arg(long, short)]
     pretty: bool,
-    /// Disable the count annotation
+    /// Read a stream of documents, expanding each document independently
+    ///
+    /// Reads newline-delimited JSON or a multi-document YAML stream. Each
+    /// expanded JSON document is written on its own line.
+    #[arg(long, conflicts_with = "pretty")]
+    ndjson: bool,
+    /// Read every JSON document in the input into one array before expanding it
+    #[arg(long, conflicts_with_all = ["ndjson", "stream_items"])]
+    slurp: bool,
+    /// Treat the JSON documents in the input as the items of one array,
+    /// writing each annotated item on its own line as it is read
+    ///
+    /// The count annotation is not available in this mode.
+    #[arg(long, conflicts_with_all = ["pretty", "ndjson"])]
+    stream_items: bool,
+    /// Load settings from a TOML or JSON configuration file
+    ///
+    /// Defaults to `jxpand.toml` in the working directory if it exists.
+    /// Command line options override settings from the file.
+    #[arg(long, short, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+    /// Print the effective configuration as JSON and exit
     #[arg(long)]
+    print_config: bool,
+    /// Enable the count annotation if a configuration file disables it
+    #[arg(long, overrides_with = "no_count")]
+    count: bool,
+    /// Disable the count annotation
+    #[arg(long, overrides_with = "count")]
     no_count: bool,
+    /// Enable the first annotation if a configuration file disables it
+    #[arg(long, overrides_with = "no_first")]
+    first: bool,
     /// Disable the first annotation
-    #[arg(long)]
+    #[arg(long, overrides_with = "first")]
     no_first: bool,
+    /// Enable the last annotation if a configuration file disables it
+    #[arg(long, overrides_with = "no_last")]
+    last: bool,
     /// Disable the last annotation
-    #[arg(long)]
+    #[arg(long, overrides_with = "last")]
     no_last: bool,
+    /// Enable the index annotation if a configuration file disables it
+    #[arg(long, overrides_with = "no_index")]
+    index: bool,
     /// Disable the index annotation
-    #[arg(long)]
+    #[arg(long, overrides_with = "index")]
     no_index: bool,
-    /// The annotation mode to use
-    #[arg(long, value_enum, default_value_t = AnnotationMode::Wrap)]
-    mode: AnnotationMode,
-    /// The prefix to use for annotations
-    #[arg(long, default_value = "_")]
-    prefix: String,
+    /// Add an index1 annotation with the one-based position of each item
+    #[arg(long)]
+    index1: bool,
+    /// Add an rindex annotation with the number of items after each item
+    #[arg(long)]
+    rindex: bool,
+    /// Add an only annotation that is true if an item is the only item
+    #[arg(long)]
+    only: bool,
+    /// Add a middle annotation that is true for items that are neither first nor last
+    #[arg(long)]
+    middle: bool,
+    /// Add an empty annotation to arrays that is true if they have no items
+    #[arg(long)]
+    empty: bool,
+    /// Add a has_values annotation to arrays that is true if they have items
+    #[arg(long)]
+    has_values: bool,
+    /// Add an odd annotation that is true for the first, third, fifth... items
+    #[arg(long)]
+    odd: bool,
+    /// Add an even annotation that is true for the second, fourth, sixth... items
+    #[arg(long)]
+    even: bool,
+    /// Add a prev annotation holding the previous item's value
+    #[arg(long)]
+    prev: bool,
+    /// Add a next annotation holding the next item's value
+    #[arg(long)]
+    next: bool,
+    /// Only keep the given fields of object neighbours in prev and next
+    #[arg(long, value_name = "FIELDS", value_delimiter = ',')]
+    neighbour_fields: Vec<String>,
+    /// Only keep the given number of nested levels in prev and next
+    ///
+    /// A depth of 0 leaves out arrays and objects entirely.
+    #[arg(long, value_name = "DEPTH")]
+    neighbour_depth: Option<usize>,
+    /// Add a joiner annotation with the given value to all but the last item
+    ///
+    /// The value is parsed as JSON if possible, otherwise it is used as a string.
+    #[arg(long, value_name = "VALUE", value_parser = json_value)]
+    joiner: Option<serde_json::Value>,
+    /// Add a cycle annotation that rotates through a list of values
+    ///
+    /// Cycles are given as `NAME=VALUE,VALUE,...` (e.g. `row=odd,even`) and
+    /// may be repeated. Each value is parsed as JSON if possible.
+    #[arg(long = "cycle", value_name = "NAME=VALUES", value_parser = cycle)]
+    cycles: Vec<(String, Vec<serde_json::Value>)>,
+    /// Convert every object into an annotated array of key/value entries
+    #[arg(long, conflicts_with = "entries_at")]
+    entries: bool,
+    /// Convert the objects matching the given path pattern into an annotated array of entries
+    ///
+    /// May be repeated. Use an empty string to select the root object.
+    #[arg(long, value_name = "PATTERN")]
+    entries_at: Vec<PathPattern>,
+    /// Do not convert any objects into entries, even if a configuration file does
+    #[arg(long, conflicts_with_all = ["entries", "entries_at"])]
+    no_entries: bool,
+    /// Only annotate arrays and entries at locations matching the given path pattern
+    ///
+    /// Patterns are JSON pointers, where `*` matches any key or index and `**`
+    /// matches any number of segments (e.g. `/services/*/ports`), or JSONPath
+    /// expressions (e.g. `$.services.*.ports`). May be repeated.
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<PathPattern>,
+    /// Leave values at locations matching the given path pattern untouched
+    ///
+    /// Nothing within an excluded value is annotated. May be repeated.
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<PathPattern>,
+    /// Sort entries by key instead of keeping document order
+    #[arg(long, overrides_with = "no_sort_entries")]
+    sort_entries: bool,
+    /// Keep entries in document order even if a configuration file sorts them
+    #[arg(long, overrides_with = "sort_entries")]
+    no_sort_entries: bool,
+    /// The key used to hold the original value of a wrapped item [default: value]
+    #[arg(long, value_name = "KEY")]
+    value_key: Option<String>,
+    /// The key used to hold the items of an array wrapped with a count [default: values]
+    #[arg(long, value_name = "KEY")]
+    values_key: Option<String>,
+    /// The key used to hold the key of an object entry [default: key]
+    #[arg(long, value_name = "KEY")]
+    entry_key: Option<String>,
+    /// The annotation mode to use [default: wrap]
+    #[arg(long, value_enum)]
+    mode: Option<AnnotationMode>,
+    /// How to handle annotations that collide with existing keys [default: overwrite]
+    #[arg(long, value_enum)]
+    on_collision: Option<CollisionPolicy>,
+    /// Fail if arrays or objects are nested deeper than the given depth
+    ///
+    /// The root array or object has a depth of 1.
+    #[arg(long, value_name = "DEPTH")]
+    max_depth: Option<usize>,
+    /// The prefix to use for annotations [default: _]
+    #[arg(long)]
+    prefix: Option<String>,
+    /// The format of the input [default: detected from the input file, or json]
+    #[arg(long, value_enum, value_name = "FORMAT", global = true)]
+    input_format: Option<sio::Format>,
+    /// The format of the output [default: detected from the output file, or json]
+    #[arg(long, value_enum, value_name = "FORMAT", global = true)]
+    output_format: Option<sio::Format>,
+    #[cfg(feature = "csv")]
+    #[command(flatten)]
+    csv: sio::CsvOptions,
     /// The input file to use
-    #[arg(long, short, value_parser = input_path, default_value = "-")]
+    #[arg(long, short, value_parser = input_path, default_value = "-", global = true)]
     input: sio::Source,
     /// The output file to use
-    #[arg(long, short, value_parser = output_path, default_value = "-")]
+    #[arg(long, short, value_parser = output_path, default_value = "-", global = true)]
     output: sio::Destination,
 }
 
-impl From<&Cli> for Annotations {
-    fn from(cli: &Cli) -> Self {
-        let mut annotations = Annotations::default();
-        if cli.no_count {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Expand the input and render templates against it
+    Render(Render),
+}
+
+#[derive(Args, Clone, Debug)]
+struct Render {
+    /// The templates to render
+    #[arg(required = true, value_name = "TEMPLATE")]
+    templates: Vec<PathBuf>,
+    /// The template engine to render with
+    ///
+    /// Engines with their own loop helpers disable the annotations those
+    /// helpers replace by default. A configuration file, or the `--index`,
+    /// `--first` and `--last` flags, can enable them again.
+    #[arg(long, value_enum, default_value = "mustache")]
+    engine: Engine,
+    /// The directory to load partials from
+    ///
+    /// Defaults to the directory containing each template. Partials are
+    /// loaded from files with the engine's template extension (e.g.
+    /// `<name>.mustache`).
+    #[arg(long, value_name = "DIR")]
+    partials: Option<PathBuf>,
+    /// Write each template to its own file in the given directory instead of
+    /// writing all templates to the output
+    ///
+    /// Output files are named after their template without the engine's
+    /// extension, so `config.toml.mustache` is written to `config.toml`.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+}
+
+impl Render {
+    fn render(&self, data: &serde_json::Value, output: &sio::Destination) -> Result<(), Error> {
+        let renderer = self.engine.renderer(self.partials.clone());
+        match &self.output_dir {
+            Some(dir) => {
+                for template in &self.templates {
+                    let path = dir.join(self.output_name(template)?);
+                    let file = std::fs::File::create(&path).map_err(|e| {
+                        std::io::Error::new(
+                            e.kind(),
+                            format!("unable to create {}: {}", path.display(), e),
+                        )
+                    })?;
+                    let mut file = std::io::BufWriter::new(file);
+                    renderer.render(template, data, &mut file)?;
+                    file.flush()?;
+                }
+            }
+            None => {
+                let mut output = output.open()?;
+                for template in &self.templates {
+                    renderer.render(template, data, &mut output)?;
+                }
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the name of the file a template is rendered to in the output directory.
+    fn output_name<'a>(&self, template: &'a Path) -> Result<&'a std::ffi::OsStr, Error> {
+        let extensions = self.engine.extensions();
+        match (template.extension(), template.file_stem()) {
+            (Some(ext), Some(stem)) if extensions.iter().any(|e| ext == *e) => Ok(stem),
+            _ => Err(Error::config(format!(
+                "{}: templates must have a .{} extension when using --output-dir",
+                template.display(),
+                extensions.join(" or .")
+            ))),
+        }
+    }
+}
+
+impl Cli {
+    /// Loads the configuration file, if any, and applies the command line options to it.
+    fn config(&self) -> Result<Config, Error> {
+        let mut defaults = Config::default();
+        if let Some(Command::Render(render)) = &self.command {
+            render.engine.configure(&mut defaults);
+        }
+        let mut config = match &self.config {
+            Some(path) => load_config(path, defaults)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                load_config(Path::new(DEFAULT_CONFIG_FILE), defaults)?
+            }
+            None => defaults,
+        };
+        self.apply_annotations(config.annotations_mut());
+        if let Some(prefix) = &self.prefix {
+            config.set_annotation_prefix(prefix);
+        }
+        if let Some(mode) = &self.mode {
+            config.set_object_mode(mode.clone());
+        }
+        if self.entries {
+            config.set_entries(Entries::All);
+        } else if !self.entries_at.is_empty() {
+            config.set_entries(Entries::At(self.entries_at.clone()));
+        } else if self.no_entries {
+            config.set_entries(Entries::None);
+        }
+        if self.sort_entries {
+            config.set_sort_entries(true);
+        } else if self.no_sort_entries {
+            config.set_sort_entries(false);
+        }
+        if let Some(policy) = &self.on_collision {
+            config.set_collision_policy(policy.clone());
+        }
+        if let Some(max_depth) = self.max_depth {
+            config.set_max_depth(Some(max_depth));
+        }
+        if !self.include.is_empty() {
+            config.set_include(self.include.clone());
+        }
+        if !self.exclude.is_empty() {
+            config.set_exclude(self.exclude.clone());
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_annotations(&self, annotations: &mut Annotations) {
+        if self.count {
+            annotations.enable_count();
+        }
+        if self.no_count {
             annotations.disable_count();
         }
-        if cli.no_first {
+        if self.first {
+            annotations.enable_first();
+        }
+        if self.no_first {
             annotations.disable_first();
         }
-        if cli.no_last {
+        if self.last {
+            annotations.enable_last();
+        }
+        if self.no_last {
             annotations.disable_last();
         }
-        if cli.no_index {
+        if self.index {
+            annotations.enable_index();
+        }
+        if self.no_index {
             annotations.disable_index();
         }
-        annotations
+        if self.index1 {
+            annotations.enable_index1();
+        }
+        if self.rindex {
+            annotations.enable_rindex();
+        }
+        if self.only {
+            annotations.enable_only();
+        }
+        if self.middle {
+            annotations.enable_middle();
+        }
+        if self.empty {
+            annotations.enable_empty();
+        }
+        if self.has_values {
+            annotations.enable_has_values();
+        }
+        if self.odd {
+            annotations.enable_odd();
+        }
+        if self.even {
+            annotations.enable_even();
+        }
+        if self.prev {
+            annotations.enable_prev();
+        }
+        if self.next {
+            annotations.enable_next();
+        }
+        if !self.neighbour_fields.is_empty() {
+            annotations.set_neighbour_fields(self.neighbour_fields.clone());
+        }
+        if let Some(depth) = self.neighbour_depth {
+            annotations.set_neighbour_depth(Some(depth));
+        }
+        if let Some(joiner) = &self.joiner {
+            annotations.enable_joiner();
+            annotations.set_joiner_value(joiner.clone());
+        }
+        for (name, values) in &self.cycles {
+            annotations.add_cycle(name, values.clone());
+        }
+        if let Some(key) = &self.value_key {
+            annotations.set_value_key(key);
+        }
+        if let Some(key) = &self.values_key {
+            annotations.set_values_key(key);
+        }
+        if let Some(key) = &self.entry_key {
+            annotations.set_entry_key(key);
+        }
     }
 }
 
-impl From<&Cli> for Config {
-    fn from(cli: &Cli) -> Self {
-        Config::new(Annotations::from(cli), cli.prefix.clone(), cli.mode.clone())
+/// Loads a configuration file, reading it as JSON if it has a `.json` extension
+/// and as TOML otherwise, and layers the settings it contains over the given
+/// defaults.
+fn load_config(path: &Path, defaults: Config) -> Result<Config, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("unable to read {}: {}", path.display(), e),
+        )
+    })?;
+    let error = |e: &dyn std::fmt::Display| Error::config(format!("{}: {}", path.display(), e));
+    // The file is checked as a whole first so errors report where they occur.
+    let settings: serde_json::Value = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str::<Config>(&contents).map_err(|e| error(&e))?;
+        serde_json::from_str(&contents).map_err(|e| error(&e))?
+    } else {
+        toml::from_str::<Config>(&contents).map_err(|e| error(&e))?;
+        toml::from_str(&contents).map_err(|e| error(&e))?
+    };
+    let mut config = serde_json::to_value(defaults)?;
+    merge(&mut config, settings);
+    serde_json::from_value(config).map_err(|e| error(&e))
+}
+
+/// Merges the members of `settings` into `base`, recursing into objects.
+fn merge(base: &mut serde_json::Value, settings: serde_json::Value) {
+    match (base, settings) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(settings)) => {
+            for (key, value) in settings {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, settings) => *base = settings,
     }
 }
 
 mod sio {
+    #[cfg(feature = "csv")]
+    use clap::Args;
+    use clap::ValueEnum;
+    use jxpand::Error;
+    use serde_json::Value;
     use std::io;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+
+    /// A stream of documents read from an input.
+    pub type Documents<'a> = Box<dyn Iterator<Item = Result<Value, Error>> + 'a>;
+
+    /// The formats that documents can be read and written in.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+    pub enum Format {
+        #[default]
+        Json,
+        #[cfg(feature = "csv")]
+        Csv,
+        #[cfg(feature = "csv")]
+        Tsv,
+        #[cfg(feature = "json5")]
+        #[value(alias = "jsonc")]
+        Json5,
+        #[cfg(feature = "toml")]
+        Toml,
+        #[cfg(feature = "yaml")]
+        Yaml,
+    }
+
+    impl Format {
+        /// Detects the format of a file from its extension.
+        pub fn from_path(path: &Path) -> Option<Format> {
+            match path.extension()?.to_str()? {
+                "json" => Some(Format::Json),
+                #[cfg(feature = "csv")]
+                "csv" => Some(Format::Csv),
+                #[cfg(feature = "csv")]
+                "tsv" | "tab" => Some(Format::Tsv),
+                #[cfg(feature = "json5")]
+                "json5" | "jsonc" => Some(Format::Json5),
+                #[cfg(feature = "toml")]
+                "toml" => Some(Format::Toml),
+                #[cfg(feature = "yaml")]
+                "yaml" | "yml" => Some(Format::Yaml),
+                _ => None,
+            }
+        }
+
+        /// Reads the documents in the input. JSON documents are read one at a
+        /// time, YAML streams are split on `---` separators, and JSON5, TOML
+        /// and CSV input is always a single document.
+        pub fn read<'a>(self, input: impl io::Read + 'a) -> Documents<'a> {
+            match self {
+                Format::Json => Box::new(
+                    serde_json::Deserializer::from_reader(input)
+                        .into_iter::<Value>()
+                        .map(|document| document.map_err(Into::into)),
+                ),
+                #[cfg(feature = "csv")]
+                Format::Csv | Format::Tsv => CsvOptions::default().read(self, input),
+                #[cfg(feature = "json5")]
+                Format::Json5 => Box::new(std::iter::once_with(move || read_json5(input))),
+                #[cfg(feature = "toml")]
+                Format::Toml => Box::new(std::iter::once_with(move || read_toml(input))),
+                #[cfg(feature = "yaml")]
+                Format::Yaml => read_yaml(input),
+            }
+        }
+
+        /// Writes a single document to the output. JSON5 is written as plain
+        /// JSON, which is valid JSON5.
+        pub fn write(
+            self,
+            output: impl io::Write,
+            value: &Value,
+            pretty: bool,
+        ) -> Result<(), Error> {
+            match self {
+                Format::Json if pretty => serde_json::to_writer_pretty(output, value)?,
+                Format::Json => serde_json::to_writer(output, value)?,
+                #[cfg(feature = "json5")]
+                Format::Json5 => Format::Json.write(output, value, pretty)?,
+                #[cfg(feature = "csv")]
+                Format::Csv | Format::Tsv => return Err(Error::config(CSV_OUTPUT_ERROR)),
+                #[cfg(feature = "toml")]
+                Format::Toml => write_toml(output, value, pretty)?,
+                #[cfg(feature = "yaml")]
+                Format::Yaml => serde_yaml_ng::to_writer(output, value).map_err(Error::parse)?,
+            }
+            Ok(())
+        }
+
+        /// Writes a document as part of a stream: JSON documents are written
+        /// on their own line, and YAML documents start with a `---` separator.
+        pub fn write_item(self, mut output: impl io::Write, value: &Value) -> Result<(), Error> {
+            match self {
+                Format::Json => {
+                    serde_json::to_writer(&mut output, value)?;
+                    output.write_all(b"\n")?;
+                }
+                #[cfg(feature = "csv")]
+                Format::Csv | Format::Tsv => return Err(Error::config(CSV_OUTPUT_ERROR)),
+                #[cfg(feature = "json5")]
+                Format::Json5 => Format::Json.write_item(output, value)?,
+                #[cfg(feature = "toml")]
+                Format::Toml => {
+                    return Err(Error::config(
+                        "TOML cannot be written as a stream of documents",
+                    ));
+                }
+                #[cfg(feature = "yaml")]
+                Format::Yaml => {
+                    output.write_all(b"---\n")?;
+                    serde_yaml_ng::to_writer(output, value).map_err(Error::parse)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    const CSV_OUTPUT_ERROR: &str = "CSV and TSV can only be used as input formats";
+
+    /// Options for reading CSV and TSV input.
+    #[cfg(feature = "csv")]
+    #[derive(Args, Clone, Debug, Default)]
+    pub struct CsvOptions {
+        /// The field delimiter of CSV input [default: ',' for csv, tab for tsv]
+        #[arg(long, value_name = "CHAR", value_parser = delimiter, global = true)]
+        pub delimiter: Option<u8>,
+        /// Treat the first row of CSV input as data instead of a header,
+        /// naming columns by their index
+        #[arg(long, global = true)]
+        pub no_header: bool,
+        /// Read CSV fields that look like numbers or booleans as numbers or booleans
+        #[arg(long, global = true)]
+        pub infer_types: bool,
+    }
+
+    #[cfg(feature = "csv")]
+    impl CsvOptions {
+        /// Reads the rows of the input as a single array of objects keyed by
+        /// the header row.
+        pub fn read<'a>(&self, format: Format, input: impl io::Read + 'a) -> Documents<'a> {
+            let delimiter = match (self.delimiter, format) {
+                (Some(delimiter), _) => delimiter,
+                (None, Format::Tsv) => b'\t',
+                (None, _) => b',',
+            };
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .has_headers(!self.no_header)
+                .from_reader(input);
+            let infer_types = self.infer_types;
+            Box::new(std::iter::once_with(move || {
+                let headers = match reader.has_headers() {
+                    true => Some(reader.headers().map_err(Error::parse)?.clone()),
+                    false => None,
+                };
+                let mut rows = Vec::new();
+                for record in reader.records() {
+                    let record = record.map_err(Error::parse)?;
+                    let row = record
+                        .iter()
+                        .enumerate()
+                        .map(|(i, field)| {
+                            let key = match headers.as_ref().and_then(|h| h.get(i)) {
+                                Some(header) => header.to_string(),
+                                None => i.to_string(),
+                            };
+                            let value = match infer_types {
+                                true => infer_type(field),
+                                false => Value::from(field),
+                            };
+                            (key, value)
+                        })
+                        .collect();
+                    rows.push(Value::Object(row));
+                }
+                Ok(Value::Array(rows))
+            }))
+        }
+    }
+
+    /// Reads a field as a boolean, integer or float if it looks like one, and
+    /// as a string otherwise. Numbers that would not be written back exactly
+    /// as they appear, such as `00501` or integers too large to hold without
+    /// losing precision, are kept as strings.
+    #[cfg(feature = "csv")]
+    fn infer_type(field: &str) -> Value {
+        match field {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match field.parse::<serde_json::Number>() {
+                Ok(number) if number.to_string() == field => Value::Number(number),
+                _ => Value::from(field),
+            },
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    fn delimiter(s: &str) -> Result<u8, String> {
+        match s {
+            "\\t" | "tab" => Ok(b'\t'),
+            _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+            _ => Err(format!(
+                "expected a single ASCII character or 'tab', found '{}'",
+                s
+            )),
+        }
+    }
+
+    /// Reads a JSON5 document, which allows comments, trailing commas and
+    /// unquoted keys. Errors report the line and column of the problem.
+    #[cfg(feature = "json5")]
+    fn read_json5(mut input: impl io::Read) -> Result<Value, Error> {
+        let mut contents = String::new();
+        input.read_to_string(&mut contents)?;
+        json5::from_str(&contents).map_err(Error::parse)
+    }
+
+    /// Reads a stream of YAML documents. Input holding only whitespace and
+    /// comments has no documents, as with JSON, and floats that JSON cannot
+    /// represent are read as strings, as with TOML.
+    #[cfg(feature = "yaml")]
+    fn read_yaml<'a>(mut input: impl io::Read + 'a) -> Documents<'a> {
+        let mut contents = String::new();
+        if let Err(e) = input.read_to_string(&mut contents) {
+            return Box::new(std::iter::once(Err(e.into())));
+        }
+        let empty = contents
+            .lines()
+            .map(str::trim)
+            .all(|line| line.is_empty() || line.starts_with('#'));
+        if empty {
+            return Box::new(std::iter::empty());
+        }
+        let documents = serde_yaml_ng::Deserializer::from_reader(io::Cursor::new(contents));
+        Box::new(documents.map(|document| {
+            let mut value = <serde_yaml_ng::Value as serde::Deserialize>::deserialize(document)
+                .map_err(Error::parse)?;
+            finite_yaml(&mut value);
+            serde_json::to_value(value).map_err(Error::parse)
+        }))
+    }
+
+    /// Replaces the floats in a YAML value that JSON cannot represent with
+    /// strings.
+    #[cfg(feature = "yaml")]
+    fn finite_yaml(value: &mut serde_yaml_ng::Value) {
+        use serde_yaml_ng::Value as Yaml;
+        match value {
+            Yaml::Number(n) => {
+                if let Some(f) = n.as_f64().filter(|f| !f.is_finite()) {
+                    *value = Yaml::String(non_finite(f).to_string());
+                }
+            }
+            Yaml::Sequence(values) => values.iter_mut().for_each(finite_yaml),
+            Yaml::Mapping(map) => map.values_mut().for_each(finite_yaml),
+            Yaml::Tagged(tagged) => finite_yaml(&mut tagged.value),
+            _ => {}
+        }
+    }
+
+    /// Gets the TOML spelling of a float that JSON cannot represent.
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    fn non_finite(f: f64) -> &'static str {
+        match f {
+            _ if f.is_nan() => "nan",
+            _ if f > 0.0 => "inf",
+            _ => "-inf",
+        }
+    }
+
+    /// Reads a TOML document. Datetimes are read as RFC 3339 strings, and
+    /// floats that JSON cannot represent keep their TOML spelling (`nan`,
+    /// `inf` or `-inf`) as strings.
+    #[cfg(feature = "toml")]
+    fn read_toml(mut input: impl io::Read) -> Result<Value, Error> {
+        let mut contents = String::new();
+        input.read_to_string(&mut contents)?;
+        let table: toml::Table = toml::from_str(&contents).map_err(Error::parse)?;
+        Ok(from_toml(toml::Value::Table(table)))
+    }
+
+    #[cfg(feature = "toml")]
+    fn from_toml(value: toml::Value) -> Value {
+        match value {
+            toml::Value::String(s) => Value::String(s),
+            toml::Value::Integer(i) => Value::from(i),
+            toml::Value::Float(f) => match serde_json::Number::from_f64(f) {
+                Some(n) => Value::Number(n),
+                None => Value::from(non_finite(f)),
+            },
+            toml::Value::Boolean(b) => Value::Bool(b),
+            toml::Value::Datetime(d) => Value::String(d.to_string()),
+            toml::Value::Array(a) => Value::Array(a.into_iter().map(from_toml).collect()),
+            toml::Value::Table(t) => {
+                Value::Object(t.into_iter().map(|(k, v)| (k, from_toml(v))).collect())
+            }
+        }
+    }
+
+    /// Writes a value as a TOML document. The value must be an object. Null
+    /// members are left out, as TOML has no null value, and strings holding a
+    /// TOML datetime are written as datetimes, so datetimes that were read as
+    /// strings keep their type.
+    #[cfg(feature = "toml")]
+    fn write_toml(mut output: impl io::Write, value: &Value, pretty: bool) -> Result<(), Error> {
+        let table = match to_toml(value).map_err(Error::parse)? {
+            Some(toml::Value::Table(table)) => table,
+            _ => return Err(Error::parse("TOML documents must be an object")),
+        };
+        let contents = if pretty {
+            toml::to_string_pretty(&table)
+        } else {
+            toml::to_string(&table)
+        }
+        .map_err(Error::parse)?;
+        output.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    #[cfg(feature = "toml")]
+    fn to_toml(value: &Value) -> Result<Option<toml::Value>, String> {
+        let value = match value {
+            Value::Null => return Ok(None),
+            Value::Bool(b) => toml::Value::Boolean(*b),
+            Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => toml::Value::Integer(i),
+                (None, Some(f)) if n.is_f64() => toml::Value::Float(f),
+                _ => return Err(format!("{} is too large for a TOML integer", n)),
+            },
+            Value::String(s) => match s.parse::<toml::value::Datetime>() {
+                Ok(datetime) => toml::Value::Datetime(datetime),
+                Err(_) => toml::Value::String(s.clone()),
+            },
+            Value::Array(a) => toml::Value::Array(
+                a.iter()
+                    .map(|v| {
+                        to_toml(v)?.ok_or_else(|| "TOML arrays cannot contain null".to_string())
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(o) => {
+                let mut table = toml::Table::new();
+                for (k, v) in o {
+                    if let Some(v) = to_toml(v)? {
+                        table.insert(k.clone(), v);
+                    }
+                }
+                toml::Value::Table(table)
+            }
+        };
+        Ok(Some(value))
+    }
 
     #[derive(Clone, Debug)]
     pub enum Source {
@@ -107,6 +831,14 @@ mod sio {
                 Source::File(path) => Ok(Box::new(io::BufReader::new(std::fs::File::open(path)?))),
             }
         }
+
+        /// Detects the format of the source from its file extension.
+        pub fn format(&self) -> Option<Format> {
+            match self {
+                Source::Stdin => None,
+                Source::File(path) => Format::from_path(path),
+            }
+        }
     }
 
     impl Destination {
@@ -118,6 +850,14 @@ mod sio {
                 }
             }
         }
+
+        /// Detects the format of the destination from its file extension.
+        pub fn format(&self) -> Option<Format> {
+            match self {
+                Destination::Stdout => None,
+                Destination::File(path) => Format::from_path(path),
+            }
+        }
     }
 }
 
@@ -135,6 +875,44 @@ fn output_path(s: &str) -> Result<sio::Destination, String> {
     }
 }
 
+fn json_value(s: &str) -> Result<serde_json::Value, String> {
+    Ok(serde_json::from_str(s).unwrap_or_else(|_| serde_json::Value::String(s.to_string())))
+}
+
+fn cycle(s: &str) -> Result<(String, Vec<serde_json::Value>), String> {
+    let (name, values) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUES, found '{}'", s))?;
+    if name.is_empty() {
+        return Err("cycle name must not be empty".to_string());
+    }
+    let values = values
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(values);
+    if values.is_empty() {
+        return Err(format!("cycle '{}' must have at least one value", name));
+    }
+    let values = values
+        .split(',')
+        .map(json_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name.to_string(), values))
+}
+
+/// Gets the process exit code for an error, so scripts can tell failures apart.
+/// Invalid arguments exit with 2, as reported by clap.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Io(_) => 3,
+        Error::Parse(_) => 4,
+        Error::Config(_) => 5,
+        Error::Collision(_) | Error::Depth(_) => 6,
+        Error::Render(_) => 7,
+        _ => 1,
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -142,36 +920,124 @@ fn main() {
         Ok(()) => (),
         Err(e) => {
             eprintln!("{}", e);
-            std::process::exit(1);
+            std::process::exit(exit_code(&e));
         }
     }
 }
 
 impl Cli {
-    fn run(&self) -> Result<(), Box<dyn Error>> {
-        let config = Config::from(self);
+    fn run(&self) -> Result<(), Error> {
+        let config = self.config()?;
+        if self.print_config {
+            return self.write(&serde_json::to_value(&config)?);
+        }
+        let expander = JsonExpander::new(config);
 
-        let mut input = self.input.open()?;
+        if self.command.is_some() && (self.ndjson || self.stream_items) {
+            return Err(Error::config(
+                "--ndjson and --stream-items cannot be used when rendering",
+            ));
+        }
 
-        let input = serde_json::from_reader(&mut input)?;
+        let mut documents = self.documents()?;
 
-        let expander = jxpand::JsonExpander::new(config);
-        let expanded = expander.expand(input);
-        self.write_json(&expanded)?;
-        Ok(())
-    }
+        if self.ndjson || self.stream_items {
+            let mut output = self.output.open()?;
+            if self.ndjson {
+                expand_documents(&expander, documents, self.output_format(), &mut output)?;
+            } else {
+                expand_stream_items(&expander, documents, self.output_format(), &mut output)?;
+            }
+            output.flush()?;
+            return Ok(());
+        }
 
-    fn write_json(&self, value: &serde_json::Value) -> Result<(), Box<dyn Error>> {
-        let output = self.output.open()?;
-        if self.pretty {
-            serde_json::to_writer_pretty(output, value)?;
+        let input = if self.slurp {
+            serde_json::Value::Array(documents.collect::<Result<_, _>>()?)
         } else {
-            serde_json::to_writer(output, value)?;
+            let document = documents
+                .next()
+                .ok_or_else(|| Error::parse("the input contains no documents"))??;
+            if let Some(next) = documents.next() {
+                next?;
+                return Err(Error::config("the input contains more than one document; use --ndjson, --slurp or --stream-items to read a stream of documents"));
+            }
+            document
+        };
+
+        let expanded = expander.try_expand(input)?;
+        match &self.command {
+            Some(Command::Render(render)) => render.render(&expanded, &self.output),
+            None => self.write(&expanded),
         }
+    }
+
+    /// Opens the input and reads its documents.
+    fn documents(&self) -> Result<sio::Documents<'static>, Error> {
+        let input = self.input.open()?;
+        Ok(match self.input_format() {
+            #[cfg(feature = "csv")]
+            format @ (sio::Format::Csv | sio::Format::Tsv) => self.csv.read(format, input),
+            format => format.read(input),
+        })
+    }
+
+    /// Gets the input format, detecting it from the input file if not given.
+    fn input_format(&self) -> sio::Format {
+        self.input_format
+            .or_else(|| self.input.format())
+            .unwrap_or_default()
+    }
+
+    /// Gets the output format, detecting it from the output file if not given.
+    fn output_format(&self) -> sio::Format {
+        self.output_format
+            .or_else(|| self.output.format())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, value: &serde_json::Value) -> Result<(), Error> {
+        let mut output = self.output.open()?;
+        self.output_format()
+            .write(&mut output, value, self.pretty)?;
+        output.flush()?;
         Ok(())
     }
 }
 
+/// Expands each document in the input independently, writing the expanded
+/// documents as a stream. Documents are read and written one at a time.
+fn expand_documents(
+    expander: &JsonExpander,
+    documents: sio::Documents,
+    format: sio::Format,
+    mut output: impl Write,
+) -> Result<(), Error> {
+    for document in documents {
+        let expanded = expander.try_expand(document?)?;
+        format.write_item(&mut output, &expanded)?;
+    }
+    Ok(())
+}
+
+/// Expands each document in the input as an item of one array, writing the
+/// annotated items as a stream. A single document of lookahead is used to
+/// determine the last item, so the input is never buffered in full.
+fn expand_stream_items(
+    expander: &JsonExpander,
+    documents: sio::Documents,
+    format: sio::Format,
+    mut output: impl Write,
+) -> Result<(), Error> {
+    let mut documents = documents.enumerate().peekable();
+    while let Some((index, document)) = documents.next() {
+        let last = documents.peek().is_none();
+        let expanded = expander.try_expand_item(document?, index, last)?;
+        format.write_item(&mut output, &expanded)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,4 +1047,394 @@ mod tests {
         use clap::CommandFactory;
         Cli::command().debug_assert()
     }
+
+    #[test]
+    fn expand_documents_expands_each_document() {
+        let mut config = Config::default();
+        config.annotations_mut().disable_first();
+        config.annotations_mut().disable_last();
+        let expander = JsonExpander::new(config);
+        let input = "[1]\n{\"a\": [2, 3]}\n\n\"x\"\n";
+        let mut output = Vec::new();
+        let documents = sio::Format::Json.read(input.as_bytes());
+        expand_documents(&expander, documents, sio::Format::Json, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "{\"values\":[{\"value\":1,\"index\":0}],\"count\":1}\n",
+                "{\"a\":{\"values\":[{\"value\":2,\"index\":0},{\"value\":3,\"index\":1}],\"count\":2}}\n",
+                "\"x\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn expand_stream_items_annotates_documents_as_items() {
+        let mut config = Config::default();
+        config.annotations_mut().disable_index();
+        let expander = JsonExpander::new(config);
+        let mut output = Vec::new();
+        let documents = sio::Format::Json.read("\"a\"\n\"b\"\n".as_bytes());
+        expand_stream_items(&expander, documents, sio::Format::Json, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "{\"value\":\"a\",\"first\":true,\"last\":false}\n",
+                "{\"value\":\"b\",\"first\":false,\"last\":true}\n",
+            )
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn expand_documents_reads_and_writes_yaml_streams() {
+        let mut config = Config::default();
+        config.annotations_mut().disable_first();
+        config.annotations_mut().disable_last();
+        let expander = JsonExpander::new(config);
+        let input = "kind: List\nitems: [a]\n---\n- 1\n";
+        let mut output = Vec::new();
+        let documents = sio::Format::Yaml.read(input.as_bytes());
+        expand_documents(&expander, documents, sio::Format::Yaml, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "---\n",
+                "kind: List\n",
+                "items:\n",
+                "  values:\n",
+                "  - value: a\n",
+                "    index: 0\n",
+                "  count: 1\n",
+                "---\n",
+                "values:\n",
+                "- value: 1\n",
+                "  index: 0\n",
+                "count: 1\n",
+            )
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_reads_non_finite_floats_and_empty_streams() {
+        let input = "a: .inf\nb: -.inf\nc: .nan\nd: 1.5\n";
+        let document = sio::Format::Yaml.read(input.as_bytes()).next().unwrap();
+        assert_eq!(
+            document.unwrap(),
+            serde_json::json!({"a": "inf", "b": "-inf", "c": "nan", "d": 1.5})
+        );
+
+        for input in ["", "\n  \n", "# nothing here\n"] {
+            assert!(sio::Format::Yaml.read(input.as_bytes()).next().is_none());
+        }
+        let documents: Vec<_> = sio::Format::Yaml.read("---\n".as_bytes()).collect();
+        assert_eq!(documents.len(), 1);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trips_through_expansion() {
+        let mut config = Config::default();
+        config.annotations_mut().disable_count();
+        config.annotations_mut().disable_index();
+        config.annotations_mut().enable_joiner();
+        let expander = JsonExpander::new(config);
+        let input = concat!(
+            "released = 2024-01-02T03:04:05Z\n",
+            "day = 1979-05-27\n",
+            "ratio = 1.0\n",
+            "limit = inf\n",
+            "\n",
+            "[[bin]]\n",
+            "name = \"a\"\n",
+            "\n",
+            "[[bin]]\n",
+            "name = \"b\"\n",
+        );
+        let document = sio::Format::Toml.read(input.as_bytes()).next().unwrap();
+        let expanded = expander.try_expand(document.unwrap()).unwrap();
+        assert_eq!(expanded["released"], "2024-01-02T03:04:05Z");
+        assert_eq!(expanded["day"], "1979-05-27");
+        assert_eq!(expanded["ratio"], 1.0);
+        assert_eq!(expanded["limit"], "inf");
+        assert_eq!(expanded["bin"][0]["joiner"], ",");
+        assert!(expanded["bin"][1]["joiner"].is_null());
+
+        let mut output = Vec::new();
+        sio::Format::Toml
+            .write(&mut output, &expanded, false)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "released = 2024-01-02T03:04:05Z\n",
+                "day = 1979-05-27\n",
+                "ratio = 1.0\n",
+                "limit = \"inf\"\n",
+                "\n",
+                "[[bin]]\n",
+                "first = true\n",
+                "last = false\n",
+                "joiner = \",\"\n",
+                "\n",
+                "[bin.value]\n",
+                "name = \"a\"\n",
+                "\n",
+                "[[bin]]\n",
+                "first = false\n",
+                "last = true\n",
+                "\n",
+                "[bin.value]\n",
+                "name = \"b\"\n",
+            )
+        );
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn json5_reads_comments_and_trailing_commas() {
+        let input = "{\n  // The colours\n  colours: ['red', 'blue',],\n}\n";
+        let document = sio::Format::Json5.read(input.as_bytes()).next().unwrap();
+        assert_eq!(
+            document.unwrap(),
+            serde_json::json!({"colours": ["red", "blue"]})
+        );
+
+        let document = sio::Format::Json5
+            .read("{\n  a: [1,, 2],\n}".as_bytes())
+            .next();
+        let error = document.unwrap().unwrap_err().to_string();
+        assert!(error.ends_with("at line 2 column 9"), "{}", error);
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_rows_become_annotated_objects() {
+        let mut config = Config::default();
+        config.set_object_mode(AnnotationMode::Merge);
+        config.annotations_mut().disable_count();
+        let expander = JsonExpander::new(config);
+        let options = sio::CsvOptions {
+            infer_types: true,
+            ..Default::default()
+        };
+        let input = "name,code,active,rate\nred,1,true,0.5\nblue,2,false,x\n";
+        let document = options.read(sio::Format::Csv, input.as_bytes()).next();
+        let expanded = expander.try_expand(document.unwrap().unwrap()).unwrap();
+        assert_eq!(
+            expanded,
+            serde_json::json!([
+                {"name": "red", "code": 1, "active": true, "rate": 0.5, "_index": 0, "_first": true, "_last": false},
+                {"name": "blue", "code": 2, "active": false, "rate": "x", "_index": 1, "_first": false, "_last": true},
+            ])
+        );
+
+        let options = sio::CsvOptions {
+            no_header: true,
+            ..Default::default()
+        };
+        let document = options.read(sio::Format::Tsv, "a\t1\n".as_bytes()).next();
+        assert_eq!(
+            document.unwrap().unwrap(),
+            serde_json::json!([{"0": "a", "1": "1"}])
+        );
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_inference_keeps_inexact_numbers_as_strings() {
+        let options = sio::CsvOptions {
+            infer_types: true,
+            ..Default::default()
+        };
+        let input = "zip,id,big,ratio\n00501,12345678901234567890,123456789012345678901234,1e3\n";
+        let document = options.read(sio::Format::Csv, input.as_bytes()).next();
+        assert_eq!(
+            document.unwrap().unwrap(),
+            serde_json::json!([{
+                "zip": "00501",
+                "id": 12345678901234567890u64,
+                "big": "123456789012345678901234",
+                "ratio": "1e3",
+            }])
+        );
+    }
+
+    #[test]
+    fn errors_map_to_distinct_exit_codes() {
+        let cli = Cli::parse_from(["jxpand", "--max-depth", "1"]);
+        let expander = JsonExpander::new(cli.config().unwrap());
+        let error = expander.try_expand(serde_json::json!([[1]])).unwrap_err();
+        assert_eq!(exit_code(&error), 6);
+
+        let documents = sio::Format::Json.read("[1,".as_bytes());
+        let error = expand_documents(&expander, documents, sio::Format::Json, Vec::new());
+        assert_eq!(exit_code(&error.unwrap_err()), 4);
+
+        let error = load_config(Path::new("does-not-exist.toml"), Config::default()).unwrap_err();
+        assert_eq!(exit_code(&error), 3);
+
+        let cli = Cli::parse_from(["jxpand", "--value-key", "index"]);
+        assert_eq!(exit_code(&cli.config().unwrap_err()), 5);
+    }
+
+    #[test]
+    fn render_templates_with_partials() {
+        let dir = std::env::temp_dir().join(format!("jxpand-render-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("list.txt.mustache");
+        std::fs::write(
+            &template,
+            "{{#values}}{{> item}}{{^last}}, {{/last}}{{/values}}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("item.mustache"), "{{index}}={{value}}").unwrap();
+
+        let render = Render {
+            templates: vec![template],
+            engine: Engine::Mustache,
+            partials: None,
+            output_dir: Some(dir.clone()),
+        };
+        let data = jxpand::expand_json(serde_json::json!(["a", "b"]));
+        let result = render.render(&data, &sio::Destination::Stdout);
+        let rendered = std::fs::read_to_string(dir.join("list.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(rendered.unwrap(), "0=a, 1=b");
+    }
+
+    #[cfg(feature = "handlebars")]
+    #[test]
+    fn render_handlebars_with_engine_defaults() {
+        let dir = std::env::temp_dir().join(format!("jxpand-hbs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("list.txt.hbs");
+        std::fs::write(
+            &template,
+            "{{count}}:{{#each values}}{{> item}}{{#unless @last}}, {{/unless}}{{/each}}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("item.hbs"), "{{@index}}={{this}}").unwrap();
+
+        let cli = Cli::parse_from(
+            ["jxpand", "render", "--engine", "handlebars"]
+                .into_iter()
+                .chain(template.to_str()),
+        );
+        let config = cli.config().unwrap();
+        assert!(!config.annotations().index().is_enabled());
+        assert!(config.annotations().count().is_enabled());
+
+        let Some(Command::Render(render)) = &cli.command else {
+            panic!("expected the render command");
+        };
+        let render = Render {
+            output_dir: Some(dir.clone()),
+            ..render.clone()
+        };
+        let data = JsonExpander::new(config)
+            .try_expand(serde_json::json!(["a", "b"]))
+            .unwrap();
+        let result = render.render(&data, &sio::Destination::Stdout);
+        let rendered = std::fs::read_to_string(dir.join("list.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(rendered.unwrap(), "2:0=a, 1=b");
+    }
+
+    #[test]
+    fn cli_options_override_config_file() {
+        let dir = std::env::temp_dir().join(format!("jxpand-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        std::fs::write(
+            &path,
+            "annotation_prefix = \"$\"\nobject_mode = \"merge\"\nentries = \"all\"\nsort_entries = true\n[annotations]\ncount = false\n",
+        )
+        .unwrap();
+
+        let cli = Cli::parse_from([
+            "jxpand",
+            "--config",
+            path.to_str().unwrap(),
+            "--prefix",
+            "@",
+            "--no-first",
+            "--count",
+            "--no-entries",
+            "--no-sort-entries",
+        ]);
+        let config = cli.config().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(config.object_mode(), AnnotationMode::Merge));
+        assert_eq!(config.annotation_prefix(), "@");
+        assert!(config.annotations().count().is_enabled());
+        assert!(!config.annotations().first().is_enabled());
+        assert!(config.annotations().last().is_enabled());
+        assert!(matches!(config.entries(), Entries::None));
+        assert!(!config.sort_entries());
+    }
+
+    #[cfg(feature = "handlebars")]
+    #[test]
+    fn engine_defaults_apply_under_config_file() {
+        let dir = std::env::temp_dir().join(format!("jxpand-engine-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        std::fs::write(
+            &path,
+            "annotation_prefix = \"@\"\n[annotations]\nfirst = true\nindex = { annotation = \"i\" }\n",
+        )
+        .unwrap();
+
+        let args = ["jxpand", "--config", path.to_str().unwrap()];
+        let cli =
+            Cli::parse_from(
+                args.into_iter()
+                    .chain(["render", "--engine", "handlebars", "t.hbs"]),
+            );
+        let config = cli.config();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.annotation_prefix(), "@");
+        assert!(config.annotations().first().is_enabled());
+        assert!(!config.annotations().last().is_enabled());
+        assert!(!config.annotations().index().is_enabled());
+        assert_eq!(config.annotations().index().annotation(), "i");
+    }
+
+    #[test]
+    fn printed_config_can_be_loaded_again() {
+        let dir = std::env::temp_dir().join(format!("jxpand-print-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("printed.json");
+        let cli = Cli::parse_from(["jxpand", "--mode", "merge", "--no-count"]);
+        let printed = serde_json::to_string(&cli.config().unwrap()).unwrap();
+        std::fs::write(&path, printed).unwrap();
+
+        let cli = Cli::parse_from([
+            "jxpand",
+            "--config",
+            path.to_str().unwrap(),
+            "--prefix",
+            "@",
+        ]);
+        let config = cli.config();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let expanded = JsonExpander::new(config.unwrap())
+            .try_expand(serde_json::json!([{"a": 1}]))
+            .unwrap();
+        assert_eq!(
+            expanded,
+            serde_json::json!([{"a": 1, "@index": 0, "@first": true, "@last": true}])
+        );
+        assert!(serde_json::from_str::<Config>("{\"resolved\": true}").is_err());
+    }
 }
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A JSON Pointer ([RFC 6901](https://www.rfc-editor.org/rfc/rfc6901)) identifying a value
+/// within a JSON document.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pointer {
+    segments: Vec<String>,
+}
+
+impl Pointer {
+    /// Creates a pointer to the root of a document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let root = jxpand::path::Pointer::root();
+    /// assert_eq!(root.to_string(), "");
+    /// assert!(root.is_root());
+    /// ```
+    pub fn root() -> Self {
+        Pointer::default()
+    }
+
+    /// Returns whether the pointer refers to the root of a document.
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Gets the unescaped reference tokens of the pointer.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Returns a new pointer referring to a child of this pointer.
+    ///
+    /// # Arguments
+    ///
+    /// * `segment`: The object key or array index of the child.
+    ///
+    /// returns: Pointer
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let pointer = jxpand::path::Pointer::root().child("a/b").child(0);
+    /// assert_eq!(pointer.to_string(), "/a~1b/0");
+    /// ```
+    pub fn child(&self, segment: impl ToString) -> Pointer {
+        let mut segments = self.segments.clone();
+        segments.push(segment.to_string());
+        Pointer { segments }
+    }
+}
+
+impl Display for Pointer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for segment in &self.segments {
+            write!(f, "/{}", segment.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when a string is not a valid JSON Pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePointerError {
+    pointer: String,
+}
+
+impl Display for ParsePointerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid JSON pointer '{}': pointers must be empty or start with '/'",
+            self.pointer
+        )
+    }
+}
+
+impl std::error::Error for ParsePointerError {}
+
+impl FromStr for Pointer {
+    type Err = ParsePointerError;
+
+    /// Parses a JSON Pointer such as `/services/0/name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::path::Pointer;
+    /// let pointer: Pointer = "/a~1b/m~0n".parse().unwrap();
+    /// assert_eq!(pointer.segments(), &["a/b", "m~n"]);
+    /// assert!("a/b".parse::<Pointer>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Pointer::root());
+        }
+        match s.strip_prefix('/') {
+            Some(rest) => Ok(Pointer {
+                segments: rest
+                    .split('/')
+                    .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                    .collect(),
+            }),
+            None => Err(ParsePointerError {
+                pointer: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl Serialize for Pointer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pointer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A segment of a [`PathPattern`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PatternSegment {
+    /// Matches an object key or array index exactly.
+    Key(String),
+    /// Matches any single object key or array index.
+    Any,
+    /// Matches any number of segments, including none.
+    AnyDepth,
+}
+
+/// A pattern that matches locations within a JSON document.
+///
+/// Patterns are written either as JSON pointers, where a `*` segment matches
+/// any single key or index and a `**` segment matches any number of segments
+/// (e.g. `/services/*/ports`), or as a subset of JSONPath using `$`, `.key`,
+/// `['key']`, `[0]`, `[*]`, `.*` and `..` (e.g. `$.services.*.ports` or `$..ports`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathPattern {
+    source: String,
+    segments: Vec<PatternSegment>,
+}
+
+impl PathPattern {
+    /// Returns whether the pattern matches the given location.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The location to match.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::path::{PathPattern, Pointer};
+    /// let pattern: PathPattern = "/services/*/ports".parse().unwrap();
+    /// assert!(pattern.matches(&"/services/web/ports".parse().unwrap()));
+    /// assert!(!pattern.matches(&"/services/web/env".parse().unwrap()));
+    ///
+    /// let pattern: PathPattern = "$..ports".parse().unwrap();
+    /// assert!(pattern.matches(&"/ports".parse().unwrap()));
+    /// assert!(pattern.matches(&"/services/0/ports".parse().unwrap()));
+    /// ```
+    pub fn matches(&self, path: &Pointer) -> bool {
+        matches_segments(&self.segments, path.segments())
+    }
+
+    /// Returns a key that orders patterns from least to most specific.
+    ///
+    /// Patterns with more literal segments are more specific, followed by
+    /// patterns with more single-segment wildcards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::path::PathPattern;
+    /// let specific: PathPattern = "/services/web/ports".parse().unwrap();
+    /// let general: PathPattern = "/services/*/ports".parse().unwrap();
+    /// let broad: PathPattern = "/**/ports".parse().unwrap();
+    /// assert!(specific.specificity() > general.specificity());
+    /// assert!(general.specificity() > broad.specificity());
+    /// ```
+    pub fn specificity(&self) -> (usize, usize) {
+        let count =
+            |kind: fn(&PatternSegment) -> bool| self.segments.iter().filter(|s| kind(s)).count();
+        (
+            count(|s| matches!(s, PatternSegment::Key(_))),
+            count(|s| matches!(s, PatternSegment::Any)),
+        )
+    }
+}
+
+fn matches_segments(pattern: &[PatternSegment], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternSegment::AnyDepth, rest)) => {
+            (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            None => false,
+            Some((head, tail)) => {
+                let matched = match segment {
+                    PatternSegment::Key(key) => key == head,
+                    _ => true,
+                };
+                matched && matches_segments(rest, tail)
+            }
+        },
+    }
+}
+
+impl Display for PathPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// An error returned when a string is not a valid [`PathPattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePatternError {
+    pattern: String,
+    reason: String,
+}
+
+impl ParsePatternError {
+    fn new(pattern: &str, reason: &str) -> Self {
+        ParsePatternError {
+            pattern: pattern.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl Display for ParsePatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid path pattern '{}': {}",
+            self.pattern, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
+
+impl FromStr for PathPattern {
+    type Err = ParsePatternError;
+
+    /// Parses a path pattern written as a JSON pointer or as JSONPath.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jxpand::path::PathPattern;
+    /// let pointer: PathPattern = "/services/*/ports".parse().unwrap();
+    /// let json_path: PathPattern = "$.services[*]['ports']".parse().unwrap();
+    /// assert_eq!(pointer.specificity(), json_path.specificity());
+    /// assert!("services".parse::<PathPattern>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments = if s.starts_with('$') {
+            parse_json_path(s)?
+        } else {
+            let pointer: Pointer = s
+                .parse()
+                .map_err(|_| ParsePatternError::new(s, "expected a JSON pointer or JSONPath"))?;
+            pointer
+                .segments
+                .into_iter()
+                .map(|segment| match segment.as_str() {
+                    "*" => PatternSegment::Any,
+                    "**" => PatternSegment::AnyDepth,
+                    _ => PatternSegment::Key(segment),
+                })
+                .collect()
+        };
+        Ok(PathPattern {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+fn parse_json_path(s: &str) -> Result<Vec<PatternSegment>, ParsePatternError> {
+    let mut segments = Vec::new();
+    let mut rest = &s[1..];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("..") {
+            segments.push(PatternSegment::AnyDepth);
+            rest = after;
+            if rest.starts_with('[') {
+                continue;
+            }
+        } else if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (selector, after) = after
+                .split_once(']')
+                .ok_or_else(|| ParsePatternError::new(s, "unterminated '['"))?;
+            let segment = match selector {
+                "*" => PatternSegment::Any,
+                _ if selector.len() >= 2
+                    && (selector.starts_with('\'') && selector.ends_with('\'')
+                        || selector.starts_with('"') && selector.ends_with('"')) =>
+                {
+                    PatternSegment::Key(selector[1..selector.len() - 1].to_string())
+                }
+                _ if !selector.is_empty() && selector.chars().all(|c| c.is_ascii_digit()) => {
+                    PatternSegment::Key(selector.to_string())
+                }
+                _ => return Err(ParsePatternError::new(s, "unsupported selector")),
+            };
+            segments.push(segment);
+            rest = after;
+            continue;
+        } else {
+            return Err(ParsePatternError::new(s, "expected '.' or '['"));
+        }
+
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        let (name, after) = rest.split_at(end);
+        segments.push(match name {
+            "" => return Err(ParsePatternError::new(s, "expected a member name")),
+            "*" => PatternSegment::Any,
+            _ => PatternSegment::Key(name.to_string()),
+        });
+        rest = after;
+    }
+    Ok(segments)
+}
+
+impl Serialize for PathPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
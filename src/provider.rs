@@ -0,0 +1,421 @@
+use crate::cfg::Annotations;
+use crate::path::Pointer;
+use serde_json::Value;
+use std::fmt::Debug;
+
+/// Supplies annotations for the items of arrays and entries.
+///
+/// The built-in annotations are implemented as providers, and further
+/// providers can be registered with
+/// [`JsonExpander::with_provider`](crate::JsonExpander::with_provider).
+///
+/// # Examples
+///
+/// ```
+/// use jxpand::provider::{AnnotationProvider, ItemContext};
+/// use jxpand::Expander;
+/// use serde_json::{json, Value};
+///
+/// #[derive(Debug)]
+/// struct Square;
+///
+/// impl AnnotationProvider for Square {
+///     fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+///         let square = item.value().as_i64().map(|n| n * n);
+///         vec![(item.annotation("square"), json!(square))]
+///     }
+/// }
+///
+/// let expander = jxpand::JsonExpander::builder()
+///     .count(false)
+///     .first(false)
+///     .last(false)
+///     .build_expander()
+///     .unwrap()
+///     .with_provider(Square);
+/// let expanded = expander.expand(json!([2, 3]));
+/// assert_eq!(expanded, json!([
+///     {"value": 2, "index": 0, "square": 4},
+///     {"value": 3, "index": 1, "square": 9},
+/// ]));
+/// ```
+pub trait AnnotationProvider: Debug + Send + Sync {
+    /// Returns whether the provider adds any annotations with the given
+    /// settings. Items are only wrapped if at least one provider is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotations`: The annotation settings that apply to the array.
+    ///
+    /// returns: bool
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        let _ = annotations;
+        true
+    }
+
+    /// Returns the annotations to add to an item, as key/value pairs.
+    ///
+    /// # Arguments
+    ///
+    /// * `item`: The item being annotated and its surroundings.
+    ///
+    /// returns: Vec<(String, Value)>
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)>;
+
+    /// Returns what the provider keeps of an item before it is expanded,
+    /// which is later available to it through [`ItemContext::originals`], or
+    /// `None` if it does not need the original values. Keeping only what is
+    /// needed avoids copying every item in full.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotations`: The annotation settings that apply to the array.
+    /// * `value`: The item before it is expanded.
+    ///
+    /// returns: `Option<Value>`
+    fn original(&self, annotations: &Annotations, value: &Value) -> Option<Value> {
+        let _ = (annotations, value);
+        None
+    }
+}
+
+/// An array item or object entry that is being annotated.
+#[derive(Debug)]
+pub struct ItemContext<'a> {
+    index: usize,
+    last: bool,
+    value: &'a Value,
+    key: Option<&'a str>,
+    path: &'a Pointer,
+    parent: Option<&'a [Value]>,
+    originals: Option<&'a [Value]>,
+    annotations: &'a Annotations,
+    prefix: &'a str,
+}
+
+impl<'a> ItemContext<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        index: usize,
+        last: bool,
+        value: &'a Value,
+        key: Option<&'a str>,
+        path: &'a Pointer,
+        parent: Option<&'a [Value]>,
+        annotations: &'a Annotations,
+        prefix: &'a str,
+    ) -> Self {
+        ItemContext {
+            index,
+            last,
+            value,
+            key,
+            path,
+            parent,
+            originals: None,
+            annotations,
+            prefix,
+        }
+    }
+
+    /// Returns the item with the values a provider kept of its siblings.
+    pub(crate) fn with_originals(&self, originals: Option<&'a [Value]>) -> Self {
+        ItemContext { originals, ..*self }
+    }
+
+    /// Gets the index of the item.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// Gets whether this is the last item.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+    /// Gets the number of items, if known. The count is not known when
+    /// expanding a stream of documents one at a time.
+    pub fn count(&self) -> Option<usize> {
+        self.parent.map(<[Value]>::len)
+    }
+    /// Gets the expanded value of the item, before annotations are added.
+    pub fn value(&self) -> &Value {
+        self.value
+    }
+    /// Gets the key of the member if the item is an object entry.
+    pub fn key(&self) -> Option<&str> {
+        self.key
+    }
+    /// Gets the location of the item within the document.
+    pub fn path(&self) -> &Pointer {
+        self.path
+    }
+    /// Gets the expanded values of all items in the array or entries, if known.
+    pub fn parent(&self) -> Option<&[Value]> {
+        self.parent
+    }
+    /// Gets what the provider kept of all items in the array or entries
+    /// before they were expanded with [`AnnotationProvider::original`], if
+    /// known.
+    pub fn originals(&self) -> Option<&[Value]> {
+        self.originals
+    }
+    /// Gets the annotation settings that apply to the item, with the prefix
+    /// already applied in merge mode.
+    pub fn annotations(&self) -> &Annotations {
+        self.annotations
+    }
+
+    /// Returns the key to use for an annotation with the given name, applying
+    /// the prefix in merge mode.
+    pub fn annotation(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+}
+
+/// Returns the built-in providers in the order their annotations are added.
+pub fn builtin() -> Vec<Box<dyn AnnotationProvider>> {
+    vec![
+        Box::new(IndexProvider),
+        Box::new(PositionProvider),
+        Box::new(ParityProvider),
+        Box::new(FirstProvider),
+        Box::new(LastProvider),
+        Box::new(BoundaryProvider),
+        Box::new(JoinerProvider),
+        Box::new(NeighbourProvider),
+        Box::new(CycleProvider),
+    ]
+}
+
+/// Provides the index annotation.
+#[derive(Debug)]
+pub struct IndexProvider;
+
+impl AnnotationProvider for IndexProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.index().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let key = item.annotations.index().annotation();
+        vec![(key, Value::Number(item.index.into()))]
+    }
+}
+
+/// Provides the one-based index1 annotation and the rindex annotation,
+/// which counts the items after this one. The rindex annotation is `null`
+/// when the number of items is not known, except on the last item.
+#[derive(Debug)]
+pub struct PositionProvider;
+
+impl AnnotationProvider for PositionProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.index1().is_enabled() || annotations.rindex().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let mut annotations = Vec::new();
+        if item.annotations.index1().is_enabled() {
+            let key = item.annotations.index1().annotation();
+            annotations.push((key, Value::Number((item.index + 1).into())));
+        }
+        if item.annotations.rindex().is_enabled() {
+            let rindex = match item.count() {
+                Some(count) => Value::Number((count - item.index - 1).into()),
+                None if item.last => Value::Number(0.into()),
+                None => Value::Null,
+            };
+            annotations.push((item.annotations.rindex().annotation(), rindex));
+        }
+        annotations
+    }
+}
+
+/// Provides the odd and even annotations, based on the one-based position so
+/// the first item is odd, matching CSS `:nth-child(odd)`.
+#[derive(Debug)]
+pub struct ParityProvider;
+
+impl AnnotationProvider for ParityProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.odd().is_enabled() || annotations.even().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let odd = (item.index + 1) % 2 == 1;
+        let mut annotations = Vec::new();
+        if item.annotations.odd().is_enabled() {
+            annotations.push((item.annotations.odd().annotation(), Value::Bool(odd)));
+        }
+        if item.annotations.even().is_enabled() {
+            annotations.push((item.annotations.even().annotation(), Value::Bool(!odd)));
+        }
+        annotations
+    }
+}
+
+/// Provides the first annotation.
+#[derive(Debug)]
+pub struct FirstProvider;
+
+impl AnnotationProvider for FirstProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.first().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let key = item.annotations.first().annotation();
+        vec![(key, Value::Bool(item.index == 0))]
+    }
+}
+
+/// Provides the last annotation.
+#[derive(Debug)]
+pub struct LastProvider;
+
+impl AnnotationProvider for LastProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.last().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let key = item.annotations.last().annotation();
+        vec![(key, Value::Bool(item.last))]
+    }
+}
+
+/// Provides the only annotation, which is true if the item is both first and
+/// last, and the middle annotation, which is true if it is neither.
+#[derive(Debug)]
+pub struct BoundaryProvider;
+
+impl AnnotationProvider for BoundaryProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.only().is_enabled() || annotations.middle().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let first = item.index == 0;
+        let mut annotations = Vec::new();
+        if item.annotations.only().is_enabled() {
+            let only = Value::Bool(first && item.last);
+            annotations.push((item.annotations.only().annotation(), only));
+        }
+        if item.annotations.middle().is_enabled() {
+            let middle = Value::Bool(!first && !item.last);
+            annotations.push((item.annotations.middle().annotation(), middle));
+        }
+        annotations
+    }
+}
+
+/// Provides the joiner annotation, which is `null` on the last item.
+#[derive(Debug)]
+pub struct JoinerProvider;
+
+impl AnnotationProvider for JoinerProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.joiner().is_enabled()
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let joiner = if item.last {
+            Value::Null
+        } else {
+            item.annotations.joiner_value().clone()
+        };
+        vec![(item.annotations.joiner().annotation(), joiner)]
+    }
+}
+
+/// Provides the prev and next annotations, holding a projection of the
+/// original values of the neighbouring items. Projecting the values before
+/// they are expanded keeps annotations, including the neighbours' own prev
+/// and next, from being copied again at every level of nesting, and only the
+/// projection is kept. They are `null` for the first and last items, and
+/// when the other items are not known.
+#[derive(Debug)]
+pub struct NeighbourProvider;
+
+impl NeighbourProvider {
+    /// Projects a neighbour down to the configured fields and depth.
+    fn project(annotations: &Annotations, value: &Value) -> Value {
+        let fields = annotations.neighbour_fields();
+        let depth = annotations.neighbour_depth();
+        match value {
+            Value::Object(map) if !fields.is_empty() && depth != Some(0) => Value::Object(
+                map.iter()
+                    .filter(|(k, _)| fields.contains(k))
+                    .filter_map(|(k, v)| Some((k.clone(), truncate(v, depth.map(|d| d - 1))?)))
+                    .collect(),
+            ),
+            value => truncate(value, depth).unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Copies a value, leaving out arrays and objects nested more than `depth`
+/// levels deep.
+fn truncate(value: &Value, depth: Option<usize>) -> Option<Value> {
+    let inner = depth.map(|d| d.saturating_sub(1));
+    match value {
+        Value::Array(_) | Value::Object(_) if depth == Some(0) => None,
+        Value::Array(values) => Some(Value::Array(
+            values.iter().filter_map(|v| truncate(v, inner)).collect(),
+        )),
+        Value::Object(map) => Some(Value::Object(
+            map.iter()
+                .filter_map(|(k, v)| Some((k.clone(), truncate(v, inner)?)))
+                .collect(),
+        )),
+        value => Some(value.clone()),
+    }
+}
+
+impl AnnotationProvider for NeighbourProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.prev().is_enabled() || annotations.next().is_enabled()
+    }
+
+    fn original(&self, annotations: &Annotations, value: &Value) -> Option<Value> {
+        Some(Self::project(annotations, value))
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        let neighbour = |index: Option<usize>| {
+            let value = item.originals.zip(index).and_then(|(p, i)| p.get(i));
+            value.cloned().unwrap_or(Value::Null)
+        };
+        let mut annotations = Vec::new();
+        if item.annotations.prev().is_enabled() {
+            let prev = neighbour(item.index.checked_sub(1));
+            annotations.push((item.annotations.prev().annotation(), prev));
+        }
+        if item.annotations.next().is_enabled() {
+            let next = neighbour(Some(item.index + 1));
+            annotations.push((item.annotations.next().annotation(), next));
+        }
+        annotations
+    }
+}
+
+/// Provides the cycle annotations.
+#[derive(Debug)]
+pub struct CycleProvider;
+
+impl AnnotationProvider for CycleProvider {
+    fn is_enabled(&self, annotations: &Annotations) -> bool {
+        annotations.cycles().iter().any(|c| c.is_enabled())
+    }
+
+    fn annotate(&self, item: &ItemContext) -> Vec<(String, Value)> {
+        item.annotations
+            .cycles()
+            .iter()
+            .filter(|c| c.is_enabled())
+            .filter_map(|c| {
+                let value = c.value(item.index)?;
+                Some((c.annotation().annotation(), value.clone()))
+            })
+            .collect()
+    }
+}
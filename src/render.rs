@@ -0,0 +1,277 @@
+use crate::cfg::Config;
+use crate::error::RenderError;
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Renders templates against expanded JSON data.
+pub trait Renderer {
+    /// Renders a template file against the data, writing the result to the output.
+    ///
+    /// # Arguments
+    ///
+    /// * `template`: The path of the template to render.
+    /// * `data`: The data to render the template against, typically produced by
+    ///   [`JsonExpander`](crate::JsonExpander).
+    /// * `output`: The writer to render the template to.
+    ///
+    /// returns: Result<(), RenderError>
+    fn render(
+        &self,
+        template: &Path,
+        data: &Value,
+        output: &mut dyn Write,
+    ) -> Result<(), RenderError>;
+}
+
+/// The template engines that can be used for rendering.
+///
+/// Engines other than mustache are enabled with the cargo feature of the same name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Engine {
+    /// Mustache templates (`.mustache`).
+    #[default]
+    Mustache,
+    /// Handlebars templates (`.hbs`, `.handlebars`).
+    #[cfg(feature = "handlebars")]
+    Handlebars,
+    /// Jinja-style templates rendered with minijinja (`.j2`, `.jinja`).
+    #[cfg(feature = "minijinja")]
+    Minijinja,
+    /// Tera templates (`.tera`).
+    #[cfg(feature = "tera")]
+    Tera,
+}
+
+impl Engine {
+    /// Creates a renderer for the engine.
+    ///
+    /// # Arguments
+    ///
+    /// * `partials`: The directory to load partials and included templates from.
+    ///   Defaults to the directory containing each template.
+    ///
+    /// returns: `Box<dyn Renderer>`
+    pub fn renderer(&self, partials: Option<PathBuf>) -> Box<dyn Renderer> {
+        match self {
+            Engine::Mustache => Box::new(MustacheRenderer { partials }),
+            #[cfg(feature = "handlebars")]
+            Engine::Handlebars => Box::new(HandlebarsRenderer { partials }),
+            #[cfg(feature = "minijinja")]
+            Engine::Minijinja => Box::new(MinijinjaRenderer { partials }),
+            #[cfg(feature = "tera")]
+            Engine::Tera => Box::new(TeraRenderer { partials }),
+        }
+    }
+
+    /// Gets the file extensions used for templates of the engine.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Engine::Mustache => &["mustache"],
+            #[cfg(feature = "handlebars")]
+            Engine::Handlebars => &["hbs", "handlebars"],
+            #[cfg(feature = "minijinja")]
+            Engine::Minijinja => &["j2", "jinja", "jinja2"],
+            #[cfg(feature = "tera")]
+            Engine::Tera => &["tera"],
+        }
+    }
+
+    /// Adjusts a configuration to suit the engine.
+    ///
+    /// Engines with their own loop helpers do not need the item annotations
+    /// those helpers provide, so the index, first and last annotations are
+    /// disabled for handlebars (`@index`, `@first`, `@last`), minijinja and tera
+    /// (`loop.index0`, `loop.first`, `loop.last`). The count annotation is kept
+    /// so arrays, including the root, can still be iterated by name. Mustache is
+    /// left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: The configuration to adjust.
+    pub fn configure(&self, config: &mut Config) {
+        if *self != Engine::Mustache {
+            let annotations = config.annotations_mut();
+            annotations.disable_index();
+            annotations.disable_first();
+            annotations.disable_last();
+        }
+    }
+}
+
+fn read_template(template: &Path) -> Result<String, RenderError> {
+    std::fs::read_to_string(template)
+        .map_err(|e| RenderError::new(template, format!("unable to read template: {}", e)))
+}
+
+fn partials_dir(partials: &Option<PathBuf>, template: &Path) -> PathBuf {
+    match partials {
+        Some(dir) => dir.clone(),
+        None => match template.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+    }
+}
+
+/// Lists the files in a directory with one of the given extensions, along
+/// with their names without the extension.
+#[cfg(any(feature = "handlebars", feature = "tera"))]
+fn partial_files(
+    dir: &Path,
+    extensions: &[&str],
+    template: &Path,
+) -> Result<Vec<(String, PathBuf)>, RenderError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        RenderError::new(
+            template,
+            format!("unable to read partials from {}: {}", dir.display(), e),
+        )
+    })?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| RenderError::new(template, e))?.path();
+        let matches = path
+            .extension()
+            .is_some_and(|ext| extensions.iter().any(|e| ext == *e));
+        if let (true, Some(stem)) = (matches, path.file_stem()) {
+            files.push((stem.to_string_lossy().into_owned(), path.clone()));
+        }
+    }
+    Ok(files)
+}
+
+/// Renders mustache templates. Partials are loaded from `<name>.mustache` files.
+pub struct MustacheRenderer {
+    partials: Option<PathBuf>,
+}
+
+impl Renderer for MustacheRenderer {
+    fn render(
+        &self,
+        template: &Path,
+        data: &Value,
+        mut output: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let source = read_template(template)?;
+        let compiled = mustache::Context::new(partials_dir(&self.partials, template))
+            .compile(source.chars())
+            .map_err(|e| RenderError::new(template, format!("{:?}", e)))?;
+        compiled
+            .render(&mut output, data)
+            .map_err(|e| RenderError::new(template, e))
+    }
+}
+
+/// Renders handlebars templates. Partials are registered from the `.hbs` and
+/// `.handlebars` files in the partials directory, named without the extension.
+#[cfg(feature = "handlebars")]
+pub struct HandlebarsRenderer {
+    partials: Option<PathBuf>,
+}
+
+#[cfg(feature = "handlebars")]
+impl Renderer for HandlebarsRenderer {
+    fn render(
+        &self,
+        template: &Path,
+        data: &Value,
+        output: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let mut registry = handlebars::Handlebars::new();
+        let dir = partials_dir(&self.partials, template);
+        for (name, path) in partial_files(&dir, Engine::Handlebars.extensions(), template)? {
+            registry
+                .register_partial(&name, read_template(&path)?)
+                .map_err(|e| RenderError::new(&path, e))?;
+        }
+        registry
+            .register_template_string("template", read_template(template)?)
+            .map_err(|e| RenderError::new(template, e))?;
+        registry
+            .render_to_write("template", data, output)
+            .map_err(|e| RenderError::new(template, e))
+    }
+}
+
+/// Renders Jinja-style templates with minijinja. Included templates are loaded
+/// by path relative to the partials directory.
+#[cfg(feature = "minijinja")]
+pub struct MinijinjaRenderer {
+    partials: Option<PathBuf>,
+}
+
+#[cfg(feature = "minijinja")]
+impl Renderer for MinijinjaRenderer {
+    fn render(
+        &self,
+        template: &Path,
+        data: &Value,
+        output: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let mut env = minijinja::Environment::new();
+        env.set_keep_trailing_newline(true);
+        env.set_loader(minijinja::path_loader(partials_dir(
+            &self.partials,
+            template,
+        )));
+        let source = read_template(template)?;
+        let compiled = env
+            .template_from_str(&source)
+            .map_err(|e| RenderError::new(template, e))?;
+        let rendered = compiled
+            .render(data)
+            .map_err(|e| RenderError::new(template, e))?;
+        output
+            .write_all(rendered.as_bytes())
+            .map_err(|e| RenderError::new(template, e))
+    }
+}
+
+/// Renders tera templates. Included templates are registered from the `.tera`
+/// files in the partials directory under their file name.
+#[cfg(feature = "tera")]
+pub struct TeraRenderer {
+    partials: Option<PathBuf>,
+}
+
+#[cfg(feature = "tera")]
+impl Renderer for TeraRenderer {
+    fn render(
+        &self,
+        template: &Path,
+        data: &Value,
+        output: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let mut tera = tera::Tera::default();
+        let dir = partials_dir(&self.partials, template);
+        for (_, path) in partial_files(&dir, Engine::Tera.extensions(), template)? {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+            tera.add_template_file(&path, name.as_deref())
+                .map_err(|e| RenderError::new(&path, error_chain(&e)))?;
+        }
+        tera.add_raw_template("template", &read_template(template)?)
+            .map_err(|e| RenderError::new(template, error_chain(&e)))?;
+        let context = tera::Context::from_serialize(data)
+            .map_err(|e| RenderError::new(template, error_chain(&e)))?;
+        tera.render_to("template", &context, output)
+            .map_err(|e| RenderError::new(template, error_chain(&e)))
+    }
+}
+
+/// Describes an error along with its sources, as tera reports the cause of a
+/// failure through the source chain rather than its message.
+#[cfg(feature = "tera")]
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}